@@ -0,0 +1,1093 @@
+//! Arguments of the `#[compact(...)]` attribute.
+use crate::mapping::Mapping;
+use syn::ext::IdentExt;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{
+    bracketed, parenthesized, Attribute, Error, Ident, Lit, LitInt, LitStr, Meta, NestedMeta, Token,
+};
+
+/// Default lock file, relative to `CARGO_MANIFEST_DIR`
+const DEFAULT_LOCK: &str = "compact.lock";
+
+/// A single `key`, `key = literal`, `key = [literal, ...]` or `key(...)` argument
+pub(crate) struct Arg {
+    pub key: Ident,
+    pub value: ArgValue,
+}
+
+pub(crate) enum ArgValue {
+    Flag,
+    Lit(Lit),
+    Array(Vec<Lit>),
+    List(Vec<Arg>),
+}
+
+impl Parse for Arg {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let key = Ident::parse_any(input)?;
+        let value = if input.peek(Token![=]) {
+            input.parse::<Token![=]>()?;
+            if input.peek(syn::token::Bracket) {
+                let content;
+                bracketed!(content in input);
+                let lits = Punctuated::<Lit, Token![,]>::parse_terminated(&content)?;
+                ArgValue::Array(lits.into_iter().collect())
+            } else {
+                ArgValue::Lit(input.parse()?)
+            }
+        } else if input.peek(syn::token::Paren) {
+            let content;
+            parenthesized!(content in input);
+            ArgValue::List(parse_args(&content)?)
+        } else {
+            ArgValue::Flag
+        };
+        Ok(Self { key, value })
+    }
+}
+
+/// Parse a comma separated list of arguments
+pub(crate) fn parse_args(input: ParseStream) -> syn::Result<Vec<Arg>> {
+    Ok(Punctuated::<Arg, Token![,]>::parse_terminated(input)?
+        .into_iter()
+        .collect())
+}
+
+impl Arg {
+    /// `key = "string"`
+    pub fn string(self) -> syn::Result<LitStr> {
+        match self.value {
+            ArgValue::Lit(Lit::Str(lit)) => Ok(lit),
+            _ => Err(self.expected("a string, e.g. `key = \"value\"`")),
+        }
+    }
+
+    /// `key`
+    pub fn flag(self) -> syn::Result<bool> {
+        match self.value {
+            ArgValue::Flag => Ok(true),
+            ArgValue::Lit(Lit::Bool(lit)) => Ok(lit.value),
+            _ => Err(self.expected("no value")),
+        }
+    }
+
+    /// `key = ["a", "b"]`
+    pub fn strings(self) -> syn::Result<Vec<LitStr>> {
+        let lits = match &self.value {
+            ArgValue::Array(lits) => lits,
+            _ => return Err(self.expected("a list of strings, e.g. `key = [\"a\", \"b\"]`")),
+        };
+        lits.iter()
+            .map(|lit| match lit {
+                Lit::Str(lit) => Ok(lit.clone()),
+                lit => Err(Error::new(lit.span(), "expected a string")),
+            })
+            .collect()
+    }
+
+    /// `key = 123`
+    pub fn int<N>(self) -> syn::Result<N>
+    where
+        N: std::str::FromStr,
+        N::Err: std::fmt::Display,
+    {
+        match &self.value {
+            ArgValue::Lit(Lit::Int(lit)) => lit.base10_parse(),
+            _ => Err(self.expected("an integer, e.g. `key = 1`")),
+        }
+    }
+
+    pub fn expected(&self, what: &str) -> Error {
+        Error::new(self.key.span(), format!("`{}` expects {}", self.key, what))
+    }
+}
+
+/// Arguments of the container attribute
+const CONTAINER_KEYS: &[&str] = &[
+    "lock",
+    "lock_section",
+    "strategy",
+    "strategy_version",
+    "hash_len",
+    "hash_salt",
+    "reserved",
+    "reserved_idents",
+    "accept_verbose",
+    "previous",
+    "fingerprint",
+    "keep",
+    "min_len",
+    "shorter_only",
+    "fields",
+    "variants",
+    "pools",
+    "profile",
+    "export",
+    "alphabet",
+    "format",
+    "keys",
+];
+
+/// Container arguments that may be given more than once
+const REPEATABLE_KEYS: &[&str] = &["reserved", "reserved_idents", "previous", "keep"];
+
+/// Arguments of field and variant attributes
+const FIELD_KEYS: &[&str] = &["name", "since", "was", "keep", "priority", "hot"];
+
+fn unknown(key: &Ident, known: &[&str]) -> Error {
+    let name = key.to_string();
+    let msg = if known == CONTAINER_KEYS && FIELD_KEYS.contains(&name.as_str()) {
+        format!(
+            "`{}` is an argument of fields and variants, not of the type",
+            name
+        )
+    } else if known == FIELD_KEYS && CONTAINER_KEYS.contains(&name.as_str()) {
+        format!(
+            "`{}` is an argument of the type, not of fields and variants",
+            name
+        )
+    } else {
+        match suggest(&name, known) {
+            Some(similar) => format!(
+                "unknown compact argument `{}`, did you mean `{}`?",
+                name, similar
+            ),
+            None => format!(
+                "unknown compact argument `{}`, expected one of: {}",
+                name,
+                known.join(", ")
+            ),
+        }
+    };
+    Error::new(key.span(), msg)
+}
+
+/// Reject an argument given twice
+fn once(seen: &mut Vec<String>, key: &Ident) -> syn::Result<()> {
+    let name = key.to_string();
+    if seen.contains(&name) {
+        return Err(Error::new(
+            key.span(),
+            format!("duplicate compact argument `{}`", name),
+        ));
+    }
+    seen.push(name);
+    Ok(())
+}
+
+/// Closest known value within a few typos
+fn suggest<'a>(value: &str, known: &[&'a str]) -> Option<&'a str> {
+    known
+        .iter()
+        .map(|candidate| (distance(value, candidate), *candidate))
+        .filter(|(distance, candidate)| *distance <= (candidate.len() / 3).max(1))
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+/// Levenshtein distance counting a swap of adjacent characters as one edit
+fn distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut rows: Vec<Vec<usize>> = vec![(0..=b.len()).collect()];
+    for i in 1..=a.len() {
+        let mut row = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let above = &rows[i - 1];
+            row[j] = (above[j - 1] + usize::from(a[i - 1] != b[j - 1]))
+                .min(above[j] + 1)
+                .min(row[j - 1] + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                row[j] = row[j].min(rows[i - 2][j - 2] + 1);
+            }
+        }
+        rows.push(row);
+    }
+    rows[a.len()][b.len()]
+}
+
+/// Order in which identifiers get names
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Strategy {
+    /// Sorted by identifier
+    Alphabetical,
+    /// Source order, optionally grouped with `#[compact(since = N)]`
+    Declaration,
+    /// Derived from a hash of the identifier
+    Hash,
+    /// Abbreviations of the identifier, e.g. `ei` for `event_id`
+    Mnemonic,
+}
+
+impl Strategy {
+    const ALL: &'static [Strategy] = &[
+        Strategy::Alphabetical,
+        Strategy::Declaration,
+        Strategy::Hash,
+        Strategy::Mnemonic,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Strategy::Alphabetical => "alphabetical",
+            Strategy::Declaration => "declaration",
+            Strategy::Hash => "hash",
+            Strategy::Mnemonic => "mnemonic",
+        }
+    }
+
+    fn parse(lit: &LitStr) -> syn::Result<Self> {
+        one_of("strategy", lit, Self::ALL, Self::as_str)
+    }
+}
+
+impl Default for Strategy {
+    fn default() -> Self {
+        Strategy::Alphabetical
+    }
+}
+
+/// Which identifiers share names
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Pools {
+    /// Fields and variants
+    Shared,
+    /// Variants and fields separately
+    Split,
+    /// Variants, and fields of each variant separately
+    PerVariant,
+}
+
+impl Pools {
+    const ALL: &'static [Pools] = &[Pools::Shared, Pools::Split, Pools::PerVariant];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Pools::Shared => "shared",
+            Pools::Split => "split",
+            Pools::PerVariant => "per-variant",
+        }
+    }
+
+    fn parse(lit: &LitStr) -> syn::Result<Self> {
+        one_of("pools", lit, Self::ALL, Self::as_str)
+    }
+}
+
+/// Parse a string value out of a fixed set
+fn one_of<T: Copy>(
+    key: &str,
+    lit: &LitStr,
+    values: &[T],
+    as_str: fn(T) -> &'static str,
+) -> syn::Result<T> {
+    let value = lit.value();
+    if let Some(found) = values.iter().copied().find(|v| as_str(*v) == value) {
+        return Ok(found);
+    }
+    let names: Vec<&str> = values.iter().map(|v| as_str(*v)).collect();
+    let msg = match suggest(&value, &names) {
+        Some(similar) => format!(
+            "unknown {} \"{}\", did you mean \"{}\"?",
+            key, value, similar
+        ),
+        None => {
+            let names: Vec<String> = names.iter().map(|name| format!("\"{}\"", name)).collect();
+            format!("`{}` must be one of {}", key, names.join(", "))
+        }
+    };
+    Err(Error::new(lit.span(), msg))
+}
+
+/// Arguments of the container attribute, e.g. `#[compact(lock)]`
+pub(crate) struct ContainerArgs {
+    /// Lock file path relative to `CARGO_MANIFEST_DIR`
+    pub lock: Option<LitStr>,
+    /// Lock file section of the type, instead of its name
+    pub lock_section: Option<LitStr>,
+    pub strategy: Strategy,
+    /// Revision of the naming algorithm
+    pub version: u8,
+    /// Longest name `strategy = "hash"` may grow to on collisions
+    pub hash_len: usize,
+    /// Mixed into the hash, e.g. a type path
+    pub hash_salt: Option<LitStr>,
+    /// Names of removed identifiers never to be assigned again
+    pub reserved: Vec<LitStr>,
+    /// Removed identifiers, their names are never assigned again
+    pub reserved_idents: Vec<LitStr>,
+    /// Deserialize original identifiers too
+    pub accept_verbose: bool,
+    /// Mapping files, or inline mappings, of previous versions to deserialize
+    pub previous: Vec<Previous>,
+    /// Expected `COMPACT_FINGERPRINT`
+    pub fingerprint: Option<LitInt>,
+    /// Identifiers serialized as declared
+    pub keep: Vec<LitStr>,
+    /// Identifiers shorter than this are kept
+    pub min_len: usize,
+    /// Keep identifiers unless names are shorter
+    pub shorter_only: bool,
+    pub scope: Scope,
+    pub pools: Pools,
+    /// Sample payloads, frequent identifiers are named first
+    pub profile: Option<LitStr>,
+    /// Mapping file to write the names to
+    pub export: Option<LitStr>,
+    pub alphabet: Alphabet,
+    pub format: Format,
+    pub keys: Keys,
+}
+
+impl ContainerArgs {
+    /// Characters of generated names
+    pub fn chars(&self) -> &[char] {
+        match self.keys {
+            Keys::Strings => &self.alphabet.chars,
+            Keys::Numeric | Keys::Integer => &DIGITS,
+        }
+    }
+}
+
+const DIGITS: [char; 10] = ['0', '1', '2', '3', '4', '5', '6', '7', '8', '9'];
+
+/// Kind of generated names
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Keys {
+    /// Strings of the alphabet
+    Strings,
+    /// Decimal strings, always positional: "9" is followed by "10"
+    Numeric,
+    /// Integers, with generated Serialize and Deserialize impls
+    Integer,
+}
+
+impl Keys {
+    const ALL: &'static [Keys] = &[Keys::Strings, Keys::Numeric, Keys::Integer];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Keys::Strings => "strings",
+            Keys::Numeric => "numeric",
+            Keys::Integer => "integer",
+        }
+    }
+
+    fn parse(lit: &LitStr) -> syn::Result<Self> {
+        one_of("keys", lit, Self::ALL, Self::as_str)
+    }
+}
+
+/// Format the names must be valid and unambiguous in
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Format {
+    /// Any string, also MessagePack, CBOR etc.
+    Json,
+    Yaml,
+    Xml,
+    Ron,
+    /// Environment variables, e.g. with envy
+    Env,
+}
+
+/// Scalars YAML 1.1 reads as booleans or nulls
+const YAML_WORDS: &[&str] = &[
+    "y", "n", "yes", "no", "true", "false", "on", "off", "null", "~",
+];
+
+impl Format {
+    const ALL: &'static [Format] = &[
+        Format::Json,
+        Format::Yaml,
+        Format::Xml,
+        Format::Ron,
+        Format::Env,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Format::Json => "json",
+            Format::Yaml => "yaml",
+            Format::Xml => "xml",
+            Format::Ron => "ron",
+            Format::Env => "env",
+        }
+    }
+
+    fn parse(lit: &LitStr) -> syn::Result<Self> {
+        one_of("format", lit, Self::ALL, Self::as_str)
+    }
+
+    /// Whether a character may be part of names
+    fn allows_char(self, c: char) -> bool {
+        match self {
+            Format::Json => true,
+            Format::Yaml | Format::Ron => c.is_ascii_alphanumeric() || c == '_',
+            Format::Xml => c.is_ascii_alphanumeric() || "_-.".contains(c),
+            Format::Env => c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_',
+        }
+    }
+
+    /// Whether a name is read back as the same string
+    pub fn allows(self, key: &str) -> bool {
+        let first = match key.chars().next() {
+            Some(first) => first,
+            None => return false,
+        };
+        let lower = key.to_lowercase();
+        key.chars().all(|c| self.allows_char(c))
+            && match self {
+                Format::Json => true,
+                // Numbers in YAML, not identifiers in the others.
+                Format::Yaml => !first.is_ascii_digit() && !YAML_WORDS.contains(&lower.as_str()),
+                Format::Xml => {
+                    (first.is_ascii_alphabetic() || first == '_') && !lower.starts_with("xml")
+                }
+                Format::Ron => !first.is_ascii_digit() && key != "true" && key != "false",
+                Format::Env => !first.is_ascii_digit(),
+            }
+    }
+}
+
+/// Characters of generated names
+pub(crate) struct Alphabet {
+    /// Value of the argument
+    pub name: String,
+    pub chars: Vec<char>,
+}
+
+impl Alphabet {
+    const NAMES: &'static [&'static str] = &["letters", "alnum", "json-safe"];
+
+    fn parse(lit: &LitStr) -> syn::Result<Self> {
+        let name = lit.value();
+        let letters = crate::ALPHABET.iter().copied();
+        let chars: Vec<char> = match name.strip_prefix("custom:") {
+            Some(custom) => custom.chars().collect(),
+            None => match one_of("alphabet", lit, Self::NAMES, |name| name)? {
+                "letters" => letters.collect(),
+                "alnum" => letters.chain('0'..='9').collect(),
+                _ => letters
+                    .chain('0'..='9')
+                    .chain(
+                        ('!'..='~').filter(|c| !c.is_ascii_alphanumeric() && !"\"\\".contains(*c)),
+                    )
+                    .collect(),
+            },
+        };
+        if chars.len() < 2 {
+            return Err(Error::new(
+                lit.span(),
+                "alphabet needs at least 2 characters",
+            ));
+        }
+        for (i, c) in chars.iter().enumerate() {
+            if c.is_control() || c.is_whitespace() || *c == '"' || *c == '\\' {
+                return Err(Error::new(
+                    lit.span(),
+                    format!("{:?} is not allowed in names", c),
+                ));
+            }
+            if chars[..i].contains(c) {
+                return Err(Error::new(lit.span(), format!("{:?} is listed twice", c)));
+            }
+        }
+        Ok(Self { name, chars })
+    }
+}
+
+/// Kinds of identifiers to compact, the others are serialized as declared
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) struct Scope {
+    pub fields: bool,
+    pub variants: bool,
+}
+
+pub(crate) enum Previous {
+    /// Path relative to `CARGO_MANIFEST_DIR`
+    File(LitStr),
+    Inline(Mapping),
+}
+
+impl Default for ContainerArgs {
+    fn default() -> Self {
+        Self {
+            lock: None,
+            lock_section: None,
+            strategy: Strategy::default(),
+            version: 1,
+            hash_len: 3,
+            hash_salt: None,
+            reserved: Vec::new(),
+            reserved_idents: Vec::new(),
+            accept_verbose: false,
+            previous: Vec::new(),
+            fingerprint: None,
+            keep: Vec::new(),
+            min_len: 0,
+            shorter_only: false,
+            scope: Scope {
+                fields: true,
+                variants: true,
+            },
+            pools: Pools::Shared,
+            profile: None,
+            export: None,
+            alphabet: Alphabet {
+                name: "letters".to_string(),
+                chars: crate::ALPHABET.to_vec(),
+            },
+            format: Format::Json,
+            keys: Keys::Strings,
+        }
+    }
+}
+
+impl Parse for ContainerArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut args = Self::default();
+        let mut hash_arg: Option<Ident> = None;
+        let mut scope_args: Vec<(Ident, bool)> = Vec::new();
+        let mut alphabet_arg: Option<LitStr> = None;
+        let mut keys_arg: Option<LitStr> = None;
+        let mut seen = Vec::new();
+        for arg in parse_args(input)? {
+            let name = arg.key.to_string();
+            if CONTAINER_KEYS.contains(&name.as_str()) && !REPEATABLE_KEYS.contains(&name.as_str())
+            {
+                once(&mut seen, &arg.key)?;
+            }
+            match name.as_str() {
+                "lock" => {
+                    args.lock = Some(match arg.value {
+                        ArgValue::Flag => LitStr::new(DEFAULT_LOCK, arg.key.span()),
+                        _ => arg.string()?,
+                    })
+                }
+                "lock_section" => {
+                    let lit = arg.string()?;
+                    let section = lit.value();
+                    // Pools of a type are sections with a `/` suffix.
+                    if section.is_empty()
+                        || section
+                            .chars()
+                            .any(|c| c.is_whitespace() || c.is_control() || "[]/".contains(c))
+                    {
+                        return Err(Error::new(
+                            lit.span(),
+                            "`lock_section` must be a type path like \"billing::Invoice\"",
+                        ));
+                    }
+                    args.lock_section = Some(lit);
+                }
+                "strategy" => args.strategy = Strategy::parse(&arg.string()?)?,
+                "pools" => args.pools = Pools::parse(&arg.string()?)?,
+                "profile" => args.profile = Some(arg.string()?),
+                "export" => args.export = Some(arg.string()?),
+                "format" => args.format = Format::parse(&arg.string()?)?,
+                "keys" => {
+                    let lit = arg.string()?;
+                    args.keys = Keys::parse(&lit)?;
+                    keys_arg = Some(lit);
+                }
+                "alphabet" => {
+                    let lit = arg.string()?;
+                    args.alphabet = Alphabet::parse(&lit)?;
+                    alphabet_arg = Some(lit);
+                }
+                "strategy_version" => {
+                    let key = arg.key.clone();
+                    args.version = arg.int()?;
+                    if !(1..=2).contains(&args.version) {
+                        return Err(Error::new(key.span(), "`strategy_version` must be 1 or 2"));
+                    }
+                }
+                "hash_len" | "hash_salt" => {
+                    hash_arg.get_or_insert_with(|| arg.key.clone());
+                    if arg.key == "hash_len" {
+                        let key = arg.key.clone();
+                        args.hash_len = arg.int()?;
+                        if !(1..=8).contains(&args.hash_len) {
+                            return Err(Error::new(key.span(), "`hash_len` must be within 1..=8"));
+                        }
+                    } else {
+                        args.hash_salt = Some(arg.string()?);
+                    }
+                }
+                "reserved" => args.reserved.extend(arg.strings()?),
+                "reserved_idents" => args.reserved_idents.extend(arg.strings()?),
+                "keep" => args.keep.extend(arg.strings()?),
+                "min_len" => args.min_len = arg.int()?,
+                "shorter_only" => args.shorter_only = arg.flag()?,
+                "fields" | "variants" => {
+                    let key = arg.key.clone();
+                    scope_args.push((key, arg.flag()?));
+                }
+                "accept_verbose" => args.accept_verbose = arg.flag()?,
+                "fingerprint" => match arg.value {
+                    ArgValue::Lit(Lit::Int(lit)) => args.fingerprint = Some(lit),
+                    _ => return Err(arg.expected("an integer, e.g. `fingerprint = 0x1234`")),
+                },
+                "previous" => match arg.value {
+                    ArgValue::List(entries) => {
+                        let span = arg.key.span();
+                        let mut idents = Vec::new();
+                        let entries = entries
+                            .into_iter()
+                            .map(|entry| {
+                                once(&mut idents, &entry.key)?;
+                                Ok((entry.key.to_string(), entry.string()?.value()))
+                            })
+                            .collect::<syn::Result<_>>()?;
+                        args.previous.push(Previous::Inline(Mapping {
+                            span,
+                            entries,
+                            path: None,
+                        }));
+                    }
+                    ArgValue::Array(_) => {
+                        args.previous
+                            .extend(arg.strings()?.into_iter().map(Previous::File));
+                    }
+                    _ => args.previous.push(Previous::File(arg.string()?)),
+                },
+                _ => return Err(unknown(&arg.key, CONTAINER_KEYS)),
+            }
+        }
+        // `fields` or `variants` compact only the kinds given, `= false` leaves out a kind.
+        if scope_args.iter().any(|(_, enabled)| *enabled) {
+            args.scope = Scope {
+                fields: false,
+                variants: false,
+            };
+        }
+        for (key, enabled) in &scope_args {
+            if key == "fields" {
+                args.scope.fields = *enabled;
+            } else {
+                args.scope.variants = *enabled;
+            }
+        }
+        if let (Some((key, _)), false, false) =
+            (scope_args.last(), args.scope.fields, args.scope.variants)
+        {
+            return Err(Error::new(key.span(), "nothing is left to compact"));
+        }
+        if let (Some(section), None) = (&args.lock_section, &args.lock) {
+            return Err(Error::new(section.span(), "`lock_section` requires `lock`"));
+        }
+        if let (Some(key), false) = (hash_arg, args.strategy == Strategy::Hash) {
+            return Err(Error::new(
+                key.span(),
+                format!("`{}` requires `strategy = \"hash\"`", key),
+            ));
+        }
+        if let (Some(keys), Keys::Numeric | Keys::Integer) = (&keys_arg, args.keys) {
+            let conflict = match (&alphabet_arg, args.strategy, args.format) {
+                (Some(_), _, _) => Some("`alphabet`".to_string()),
+                (_, Strategy::Mnemonic, _) => Some("`strategy = \"mnemonic\"`".to_string()),
+                // Decimal hashes with leading zeros aren't integers.
+                (_, Strategy::Hash, _) if args.keys == Keys::Integer => {
+                    Some("`strategy = \"hash\"`".to_string())
+                }
+                (_, _, Format::Json) => None,
+                (_, _, format) => Some(format!("`format = \"{}\"`", format.as_str())),
+            };
+            if let Some(conflict) = conflict {
+                return Err(Error::new(
+                    keys.span(),
+                    format!(
+                        "{} keys can't be combined with {}",
+                        args.keys.as_str(),
+                        conflict
+                    ),
+                ));
+            }
+        }
+        // Environment variables are case-insensitive, so is the default alphabet of the format.
+        if let (Format::Env, None) = (args.format, &alphabet_arg) {
+            args.alphabet.chars.retain(|c| c.is_ascii_lowercase());
+        }
+        if let Some(alphabet) = &alphabet_arg {
+            let format = args.format;
+            let error = |msg: String| {
+                Error::new(
+                    alphabet.span(),
+                    format!("{} with format = \"{}\"", msg, format.as_str()),
+                )
+            };
+            let chars = &args.alphabet.chars;
+            if let Some(c) = chars.iter().find(|c| !format.allows_char(**c)) {
+                return Err(error(format!("{:?} is not allowed", c)));
+            }
+            // The first character is a zero digit, longer names don't start with it.
+            if !chars[1..].iter().any(|c| format.allows(&c.to_string())) {
+                return Err(error(
+                    "no character of the alphabet can start a name".to_string(),
+                ));
+            }
+        }
+        if let (Some(alphabet), Strategy::Mnemonic) = (&alphabet_arg, args.strategy) {
+            return Err(Error::new(
+                alphabet.span(),
+                "`alphabet` doesn't apply to `strategy = \"mnemonic\"`",
+            ));
+        }
+        if let (Some(profile), Strategy::Hash) = (&args.profile, args.strategy) {
+            return Err(Error::new(
+                profile.span(),
+                "`profile` doesn't apply to `strategy = \"hash\"`",
+            ));
+        }
+        if let (Some(ident), Strategy::Declaration) = (args.reserved_idents.first(), args.strategy)
+        {
+            return Err(Error::new(
+                ident.span(),
+                "declaration order of removed identifiers is unknown, reserve their names with `reserved`",
+            ));
+        }
+        Ok(args)
+    }
+}
+
+/// Arguments of field and variant attributes, e.g. `#[compact(name = "x")]`
+#[derive(Default)]
+pub(crate) struct FieldArgs {
+    /// Pinned short name
+    pub name: Option<LitStr>,
+    /// Version the identifier was added in, for `strategy = "declaration"`
+    pub since: Option<(u32, Ident)>,
+    /// Former identifier the name is assigned to
+    pub was: Option<LitStr>,
+    /// Serialized as declared
+    pub keep: Option<Ident>,
+    /// Higher priorities are named first, `hot` is the highest
+    pub priority: Option<(u32, Ident)>,
+}
+
+impl FieldArgs {
+    /// Parse all `#[compact(...)]` helper attributes of a field or variant
+    pub fn from_attrs(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut args = Self::default();
+        let mut seen = Vec::new();
+        for attr in attrs.iter().filter(|attr| is_compact(attr)) {
+            for arg in attr.parse_args_with(parse_args)? {
+                once(&mut seen, &arg.key)?;
+                match arg.key.to_string().as_str() {
+                    "name" => {
+                        let name = arg.string()?;
+                        if name.value().is_empty() {
+                            return Err(Error::new(name.span(), "name must not be empty"));
+                        }
+                        // Names are written to lock file lines.
+                        if name
+                            .value()
+                            .chars()
+                            .any(|c| c.is_whitespace() || c.is_control())
+                        {
+                            return Err(Error::new(
+                                name.span(),
+                                "name must not contain whitespace or control characters",
+                            ));
+                        }
+                        args.name = Some(name);
+                    }
+                    "since" => {
+                        let key = arg.key.clone();
+                        args.since = Some((arg.int()?, key));
+                    }
+                    "was" => {
+                        let was = arg.string()?;
+                        was.parse::<Ident>().map_err(|_| {
+                            Error::new(was.span(), "`was` expects a former identifier")
+                        })?;
+                        args.was = Some(was);
+                    }
+                    "keep" => {
+                        let key = arg.key.clone();
+                        if arg.flag()? {
+                            args.keep = Some(key);
+                        }
+                    }
+                    "priority" | "hot" => {
+                        let key = arg.key.clone();
+                        if args.priority.is_some() {
+                            return Err(Error::new(
+                                key.span(),
+                                "`hot` implies the highest priority",
+                            ));
+                        }
+                        if key == "priority" {
+                            args.priority = Some((arg.int()?, key));
+                        } else if arg.flag()? {
+                            args.priority = Some((u32::MAX, key));
+                        }
+                    }
+                    _ => return Err(unknown(&arg.key, FIELD_KEYS)),
+                }
+            }
+        }
+        if let Some(keep) = &args.keep {
+            if args.name.is_some() || args.was.is_some() || args.priority.is_some() {
+                return Err(Error::new(
+                    keep.span(),
+                    "kept identifiers can't have `name`, `was` or `priority`",
+                ));
+            }
+        }
+        Ok(args)
+    }
+
+    /// Identifier the name is assigned to
+    pub fn ident(&self, ident: &Ident) -> String {
+        match &self.was {
+            Some(was) => was.value(),
+            // Raw identifiers keep `r#`, alphabetical names are sorted by it.
+            None => ident.to_string(),
+        }
+    }
+}
+
+/// Helper attribute to be removed from the output
+pub(crate) fn is_compact(attr: &Attribute) -> bool {
+    attr.path.is_ident("compact")
+}
+
+/// Names given by `#[serde(rename)]` and `#[serde(alias)]` of a field or variant
+#[derive(Default)]
+pub(crate) struct SerdeNames {
+    pub serialize: Option<LitStr>,
+    pub deserialize: Option<LitStr>,
+    pub aliases: Vec<LitStr>,
+}
+
+impl SerdeNames {
+    pub fn from_attrs(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut names = Self::default();
+        // Malformed attributes are left to serde to report.
+        let metas = attrs
+            .iter()
+            .filter(|attr| attr.path.is_ident("serde"))
+            .filter_map(|attr| match attr.parse_meta() {
+                Ok(Meta::List(list)) => Some(list.nested),
+                _ => None,
+            })
+            .flatten();
+        for meta in metas {
+            match meta {
+                NestedMeta::Meta(Meta::NameValue(pair)) => match (&pair.lit, pair.path.get_ident())
+                {
+                    (Lit::Str(lit), Some(key)) if key == "rename" => {
+                        names.set(key, "serialize", lit)?;
+                        names.set(key, "deserialize", lit)?;
+                    }
+                    (Lit::Str(lit), Some(key)) if key == "alias" => names.aliases.push(lit.clone()),
+                    _ => {}
+                },
+                NestedMeta::Meta(Meta::List(list)) if list.path.is_ident("rename") => {
+                    for nested in list.nested {
+                        if let NestedMeta::Meta(Meta::NameValue(pair)) = nested {
+                            if let (Lit::Str(lit), Some(key)) = (&pair.lit, pair.path.get_ident()) {
+                                names.set(key, &key.to_string(), lit)?;
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(names)
+    }
+
+    fn set(&mut self, key: &Ident, side: &str, lit: &LitStr) -> syn::Result<()> {
+        let slot = match side {
+            "serialize" => &mut self.serialize,
+            "deserialize" => &mut self.deserialize,
+            _ => return Ok(()),
+        };
+        if slot.is_some() {
+            return Err(Error::new(
+                key.span(),
+                format!("duplicate `#[serde(rename)]` for {}", side),
+            ));
+        }
+        *slot = Some(lit.clone());
+        Ok(())
+    }
+
+    pub fn renamed(&self) -> bool {
+        self.serialize.is_some() || self.deserialize.is_some()
+    }
+}
+
+/// Case convention of `#[serde(rename_all)]`
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum RenameRule {
+    Lower,
+    Upper,
+    Pascal,
+    Camel,
+    Snake,
+    ScreamingSnake,
+    Kebab,
+    ScreamingKebab,
+}
+
+impl RenameRule {
+    const ALL: &'static [RenameRule] = &[
+        RenameRule::Lower,
+        RenameRule::Upper,
+        RenameRule::Pascal,
+        RenameRule::Camel,
+        RenameRule::Snake,
+        RenameRule::ScreamingSnake,
+        RenameRule::Kebab,
+        RenameRule::ScreamingKebab,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RenameRule::Lower => "lowercase",
+            RenameRule::Upper => "UPPERCASE",
+            RenameRule::Pascal => "PascalCase",
+            RenameRule::Camel => "camelCase",
+            RenameRule::Snake => "snake_case",
+            RenameRule::ScreamingSnake => "SCREAMING_SNAKE_CASE",
+            RenameRule::Kebab => "kebab-case",
+            RenameRule::ScreamingKebab => "SCREAMING-KEBAB-CASE",
+        }
+    }
+
+    /// Rename a PascalCase variant, as serde does
+    pub fn apply_to_variant(self, variant: &str) -> String {
+        match self {
+            RenameRule::Pascal => variant.to_string(),
+            RenameRule::Lower => variant.to_ascii_lowercase(),
+            RenameRule::Upper => variant.to_ascii_uppercase(),
+            RenameRule::Camel => {
+                let mut chars = variant.chars();
+                chars
+                    .next()
+                    .map(|first| first.to_ascii_lowercase().to_string() + chars.as_str())
+                    .unwrap_or_default()
+            }
+            RenameRule::Snake => {
+                let mut snake = String::new();
+                for (i, ch) in variant.char_indices() {
+                    if i > 0 && ch.is_uppercase() {
+                        snake.push('_');
+                    }
+                    snake.push(ch.to_ascii_lowercase());
+                }
+                snake
+            }
+            RenameRule::ScreamingSnake => RenameRule::Snake
+                .apply_to_variant(variant)
+                .to_ascii_uppercase(),
+            RenameRule::Kebab => RenameRule::Snake
+                .apply_to_variant(variant)
+                .replace('_', "-"),
+            RenameRule::ScreamingKebab => RenameRule::ScreamingSnake
+                .apply_to_variant(variant)
+                .replace('_', "-"),
+        }
+    }
+
+    /// Rename a snake_case field, as serde does
+    pub fn apply_to_field(self, field: &str) -> String {
+        match self {
+            RenameRule::Lower | RenameRule::Snake => field.to_string(),
+            RenameRule::Upper | RenameRule::ScreamingSnake => field.to_ascii_uppercase(),
+            RenameRule::Pascal => {
+                let mut pascal = String::new();
+                let mut capitalize = true;
+                for ch in field.chars() {
+                    if ch == '_' {
+                        capitalize = true;
+                    } else if capitalize {
+                        pascal.push(ch.to_ascii_uppercase());
+                        capitalize = false;
+                    } else {
+                        pascal.push(ch);
+                    }
+                }
+                pascal
+            }
+            RenameRule::Camel => {
+                RenameRule::Camel.apply_to_variant(&RenameRule::Pascal.apply_to_field(field))
+            }
+            RenameRule::Kebab => field.replace('_', "-"),
+            RenameRule::ScreamingKebab => field.to_ascii_uppercase().replace('_', "-"),
+        }
+    }
+}
+
+/// `#[serde(rename_all)]` or `#[serde(rename_all_fields)]` of a container
+pub(crate) struct RenameAll {
+    /// Key of the attribute, to report it
+    pub key: Ident,
+    /// Rule of serialized names, `None` if it only applies to deserialization
+    pub rule: Option<RenameRule>,
+}
+
+/// Container attributes of serde renaming its fields or variants
+#[derive(Default)]
+pub(crate) struct SerdeRules {
+    pub rename_all: Option<RenameAll>,
+    pub rename_all_fields: Option<RenameAll>,
+}
+
+impl SerdeRules {
+    pub fn from_attrs(attrs: &[Attribute]) -> Self {
+        let mut rules = Self::default();
+        // Unknown rules are left to serde to report.
+        let rule = |lit: &Lit| match lit {
+            Lit::Str(lit) => RenameRule::ALL
+                .iter()
+                .copied()
+                .find(|rule| rule.as_str() == lit.value()),
+            _ => None,
+        };
+        let metas = attrs
+            .iter()
+            .filter(|attr| attr.path.is_ident("serde"))
+            .filter_map(|attr| match attr.parse_meta() {
+                Ok(Meta::List(list)) => Some(list.nested),
+                _ => None,
+            })
+            .flatten();
+        for meta in metas {
+            let (key, rule) = match meta {
+                NestedMeta::Meta(Meta::NameValue(pair)) => match pair.path.get_ident() {
+                    Some(key) => (key.clone(), rule(&pair.lit)),
+                    None => continue,
+                },
+                NestedMeta::Meta(Meta::List(list)) => match list.path.get_ident() {
+                    Some(key) => {
+                        let serialize = list.nested.iter().find_map(|nested| match nested {
+                            NestedMeta::Meta(Meta::NameValue(pair))
+                                if pair.path.is_ident("serialize") =>
+                            {
+                                rule(&pair.lit)
+                            }
+                            _ => None,
+                        });
+                        (key.clone(), serialize)
+                    }
+                    None => continue,
+                },
+                _ => continue,
+            };
+            let slot = if key == "rename_all" {
+                &mut rules.rename_all
+            } else if key == "rename_all_fields" {
+                &mut rules.rename_all_fields
+            } else {
+                continue;
+            };
+            *slot = Some(RenameAll { key, rule });
+        }
+        rules
+    }
+}
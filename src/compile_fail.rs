@@ -0,0 +1,131 @@
+//! Misuses rejected at compile time.
+//!
+//! Typos suggest the closest argument or value, also when two letters are swapped:
+//!
+//! ```compile_fail
+//! use serde_compact::compact;
+//!
+//! #[compact(strategy = "hsah")]
+//! #[derive(serde::Serialize)]
+//! struct Event { event_id: i32 }
+//! ```
+//!
+//! ```compile_fail
+//! use serde_compact::compact;
+//!
+//! #[compact]
+//! #[derive(serde::Serialize)]
+//! struct Event { #[compact(nmae = "e")] event_id: i32 }
+//! ```
+//!
+//! A changed fingerprint lists the names that moved since the `previous` mapping:
+//!
+//! ```compile_fail
+//! use serde_compact::compact;
+//!
+//! #[compact(fingerprint = 0, previous(event_id = "e", user_id = "u"))]
+//! #[derive(serde::Serialize)]
+//! struct Event { event_id: i32, user_id: i32 }
+//! ```
+//!
+//! ```compile_fail
+//! use serde_compact::compact;
+//!
+//! #[compact(fingerprint = 0)]
+//! #[derive(serde::Serialize)]
+//! struct Event { event_id: i32, user_id: i32 }
+//! ```
+//!
+//! A name can be pinned to one identifier only, and an identifier to one name:
+//!
+//! ```compile_fail
+//! use serde_compact::compact;
+//!
+//! #[compact]
+//! #[derive(serde::Serialize)]
+//! struct Event { #[compact(name = "e")] event_id: i32, #[compact(name = "e")] user_id: i32 }
+//! ```
+//!
+//! ```compile_fail
+//! use serde_compact::compact;
+//!
+//! #[compact]
+//! #[derive(serde::Serialize)]
+//! enum Event { Start { #[compact(name = "i")] id: u32 }, Stop { #[compact(name = "d")] id: u32 } }
+//! ```
+//!
+//! Pinned names can't contain whitespace or control characters:
+//!
+//! ```compile_fail
+//! use serde_compact::compact;
+//!
+//! #[compact]
+//! #[derive(serde::Serialize)]
+//! struct Event { #[compact(name = "e\n")] event_id: i32 }
+//! ```
+//!
+//! Reserved names can't be given to live identifiers, neither pinned nor locked:
+//!
+//! ```compile_fail
+//! use serde_compact::compact;
+//!
+//! #[compact(reserved = ["a"])]
+//! #[derive(serde::Serialize)]
+//! struct Event { #[compact(name = "a")] event_id: i32 }
+//! ```
+//!
+//! ```compile_fail
+//! use serde_compact::compact;
+//!
+//! // tests/compact.lock locks `currency` to "a".
+//! #[compact(lock = "tests/compact.lock", reserved = ["a"])]
+//! #[derive(serde::Serialize)]
+//! struct Payment { currency: String, id: u32 }
+//! ```
+//!
+//! Previous names must not be current names of other identifiers:
+//!
+//! ```compile_fail
+//! use serde_compact::compact;
+//!
+//! // `user_id` is named "b" now.
+//! #[compact(previous(event_id = "b"))]
+//! #[derive(serde::Serialize)]
+//! struct Event { event_id: i32, user_id: i32 }
+//! ```
+//!
+//! Identifiers renamed with `#[serde(rename)]` already have their name:
+//!
+//! ```compile_fail
+//! use serde_compact::compact;
+//!
+//! #[compact]
+//! #[derive(serde::Serialize)]
+//! struct Event { #[serde(rename = "id")] #[compact(name = "e")] event_id: i32 }
+//! ```
+//!
+//! ```compile_fail
+//! use serde_compact::compact;
+//!
+//! #[compact]
+//! #[derive(serde::Serialize)]
+//! struct Event { #[serde(rename = "id")] #[compact(keep)] event_id: i32 }
+//! ```
+//!
+//! ```compile_fail
+//! use serde_compact::compact;
+//!
+//! #[compact]
+//! #[derive(serde::Serialize)]
+//! struct Event { #[serde(rename = "id")] event_id: i32, #[serde(rename = "id")] user_id: i32 }
+//! ```
+//!
+//! Identifiers without an abbreviation the format allows must be pinned:
+//!
+//! ```compile_fail
+//! use serde_compact::compact;
+//!
+//! #[compact(strategy = "mnemonic", format = "yaml")]
+//! #[derive(serde::Serialize)]
+//! struct Mood { ärger: i32 }
+//! ```
@@ -0,0 +1,634 @@
+//! `keys = "integer"`: Serialize and Deserialize impls with integer map keys and variant tags.
+//!
+//! Impls are generated from the `#[serde(rename)]` and `#[serde(alias)]` attributes of the compacted item,
+//! so all naming options apply. Deserialization also accepts the string form of keys, e.g. `"0"` in JSON.
+use proc_macro2::{Literal, TokenStream};
+use quote::{format_ident, quote, ToTokens};
+use syn::ext::IdentExt;
+use syn::punctuated::Punctuated;
+use syn::{
+    parse_quote, Attribute, Error, Fields, Ident, Item, Lit, Meta, NestedMeta, Path, Token, Type,
+};
+
+/// Serialized key of an identifier
+enum Key {
+    Int(u64),
+    Str(String),
+}
+
+impl Key {
+    fn new(name: String) -> Self {
+        match name.parse::<u64>() {
+            Ok(int) if int.to_string() == name => Key::Int(int),
+            _ => Key::Str(name),
+        }
+    }
+
+    fn tokens(&self) -> TokenStream {
+        match self {
+            Key::Int(int) => Literal::u64_suffixed(*int).to_token_stream(),
+            Key::Str(name) => quote!(#name),
+        }
+    }
+
+    /// Strings the key is deserialized from
+    fn strings(&self) -> String {
+        match self {
+            Key::Int(int) => int.to_string(),
+            Key::Str(name) => name.clone(),
+        }
+    }
+}
+
+/// Key and aliases of a field or variant
+struct Named {
+    ident: Ident,
+    key: Key,
+    aliases: Vec<Key>,
+}
+
+/// Reject what the generated impls can't honour, before names are assigned
+pub(crate) fn check(item: &Item) -> syn::Result<()> {
+    let (attrs, generics) = match item {
+        Item::Struct(item) => {
+            if !matches!(item.fields, Fields::Named(_)) {
+                return Err(Error::new_spanned(
+                    &item.ident,
+                    "integer keys require named fields",
+                ));
+            }
+            (&item.attrs, &item.generics)
+        }
+        Item::Enum(item) => {
+            for variant in &item.variants {
+                if let Fields::Unnamed(fields) = &variant.fields {
+                    if fields.unnamed.len() > 1 {
+                        return Err(Error::new_spanned(
+                            &variant.ident,
+                            "integer keys don't support tuple variants",
+                        ));
+                    }
+                }
+            }
+            (&item.attrs, &item.generics)
+        }
+        _ => {
+            return Err(Error::new_spanned(
+                item,
+                "integer keys require a struct or an enum",
+            ))
+        }
+    };
+    if !generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            generics,
+            "integer keys don't support generic types",
+        ));
+    }
+    let mut serde_attrs: Vec<&Attribute> = attrs.iter().filter(|a| is_serde(a)).collect();
+    match item {
+        Item::Struct(item) => serde_attrs.extend(
+            item.fields
+                .iter()
+                .flat_map(|f| &f.attrs)
+                .filter(|a| is_serde(a)),
+        ),
+        Item::Enum(item) => {
+            for variant in &item.variants {
+                serde_attrs.extend(variant.attrs.iter().filter(|a| is_serde(a)));
+                serde_attrs.extend(
+                    variant
+                        .fields
+                        .iter()
+                        .flat_map(|f| &f.attrs)
+                        .filter(|a| is_serde(a)),
+                );
+            }
+        }
+        _ => {}
+    }
+    match serde_attrs.first() {
+        Some(attr) => Err(Error::new_spanned(
+            attr,
+            "`#[serde]` attributes are not supported with integer keys",
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Replace derived Serialize and Deserialize of the compacted item with integer key impls
+pub(crate) fn expand(mut item: Item) -> syn::Result<(Item, TokenStream)> {
+    let (attrs, ident) = match &mut item {
+        Item::Struct(item) => (&mut item.attrs, item.ident.clone()),
+        Item::Enum(item) => (&mut item.attrs, item.ident.clone()),
+        _ => unreachable!("checked before"),
+    };
+    let (ser, de) = strip_derives(attrs)?;
+    if !ser && !de {
+        return Err(Error::new_spanned(
+            &ident,
+            "integer keys require `#[derive(Serialize)]` or `#[derive(Deserialize)]` after `#[compact]`",
+        ));
+    }
+    let mut impls = Vec::new();
+    match &mut item {
+        Item::Struct(item) => {
+            let (fields, types) = named_fields(&mut item.fields);
+            let path = quote!(#ident);
+            if ser {
+                let body = serialize_map(&fields, |f| quote!(&self.#f));
+                impls.push(quote! {
+                    impl ::serde::Serialize for #ident {
+                        fn serialize<__S: ::serde::Serializer>(
+                            &self,
+                            __serializer: __S,
+                        ) -> ::core::result::Result<__S::Ok, __S::Error> {
+                            #body
+                        }
+                    }
+                });
+            }
+            if de {
+                impls.push(deserialize_struct(&ident, &path, &fields, &types));
+            }
+        }
+        Item::Enum(item) => {
+            let mut variants = Vec::new();
+            let mut contents = Vec::new();
+            for variant in &mut item.variants {
+                variants.push(named(&variant.ident, &mut variant.attrs));
+                contents.push(match &mut variant.fields {
+                    Fields::Named(_) => Content::Struct(named_fields(&mut variant.fields)),
+                    Fields::Unnamed(fields) => {
+                        fields
+                            .unnamed
+                            .iter_mut()
+                            .for_each(|f| f.attrs.retain(|a| !is_serde(a)));
+                        Content::Newtype
+                    }
+                    Fields::Unit => Content::Unit,
+                });
+            }
+            if ser {
+                impls.push(serialize_enum(&ident, &variants, &contents));
+            }
+            if de {
+                impls.push(deserialize_enum(&ident, &variants, &contents));
+            }
+        }
+        _ => {}
+    }
+    let impls = quote! {
+        const _: () = {
+            #(#impls)*
+        };
+    };
+    Ok((item, impls))
+}
+
+enum Content {
+    Unit,
+    Newtype,
+    Struct((Vec<Named>, Vec<Type>)),
+}
+
+fn is_serde(attr: &Attribute) -> bool {
+    attr.path.is_ident("serde")
+}
+
+/// Remove Serialize and Deserialize from derives, returning which were derived
+fn strip_derives(attrs: &mut Vec<Attribute>) -> syn::Result<(bool, bool)> {
+    let (mut ser, mut de) = (false, false);
+    let mut stripped = Vec::new();
+    for attr in attrs.drain(..) {
+        if !attr.path.is_ident("derive") {
+            stripped.push(attr);
+            continue;
+        }
+        let paths = attr.parse_args_with(Punctuated::<Path, Token![,]>::parse_terminated)?;
+        let kept: Vec<&Path> = paths
+            .iter()
+            .filter(|path| match path.segments.last() {
+                Some(last) if last.ident == "Serialize" => {
+                    ser = true;
+                    false
+                }
+                Some(last) if last.ident == "Deserialize" => {
+                    de = true;
+                    false
+                }
+                _ => true,
+            })
+            .collect();
+        if !kept.is_empty() {
+            stripped.push(parse_quote!(#[derive(#(#kept),*)]));
+        }
+    }
+    *attrs = stripped;
+    Ok((ser, de))
+}
+
+/// Read and remove the names assigned to an identifier
+fn named(ident: &Ident, attrs: &mut Vec<Attribute>) -> Named {
+    let mut key = None;
+    let mut aliases = Vec::new();
+    for attr in attrs.iter().filter(|a| is_serde(a)) {
+        if let Ok(Meta::List(list)) = attr.parse_meta() {
+            for nested in list.nested {
+                if let NestedMeta::Meta(Meta::NameValue(pair)) = nested {
+                    if let Lit::Str(lit) = pair.lit {
+                        if pair.path.is_ident("rename") {
+                            key = Some(lit.value());
+                        } else if pair.path.is_ident("alias") {
+                            aliases.push(Key::new(lit.value()));
+                        }
+                    }
+                }
+            }
+        }
+    }
+    attrs.retain(|a| !is_serde(a));
+    Named {
+        ident: ident.clone(),
+        key: Key::new(key.unwrap_or_else(|| ident.unraw().to_string())),
+        aliases,
+    }
+}
+
+fn named_fields(fields: &mut Fields) -> (Vec<Named>, Vec<Type>) {
+    let mut named_fields = Vec::new();
+    let mut types = Vec::new();
+    for field in fields.iter_mut() {
+        if let Some(ident) = field.ident.clone() {
+            named_fields.push(named(&ident, &mut field.attrs));
+            types.push(field.ty.clone());
+        }
+    }
+    (named_fields, types)
+}
+
+/// Serialize fields as a map, `value` gives a reference to a field
+fn serialize_map(fields: &[Named], value: impl Fn(&Ident) -> TokenStream) -> TokenStream {
+    let len = fields.len();
+    let keys = fields.iter().map(|f| f.key.tokens());
+    let values = fields.iter().map(|f| value(&f.ident));
+    quote! {
+        let mut __map = ::serde::Serializer::serialize_map(
+            __serializer,
+            ::core::option::Option::Some(#len),
+        )?;
+        #(::serde::ser::SerializeMap::serialize_entry(&mut __map, &#keys, #values)?;)*
+        ::serde::ser::SerializeMap::end(__map)
+    }
+}
+
+fn serialize_enum(ident: &Ident, variants: &[Named], contents: &[Content]) -> TokenStream {
+    let arms = variants.iter().zip(contents).map(|(variant, content)| {
+        let name = &variant.ident;
+        let key = variant.key.tokens();
+        let tag = match &variant.key {
+            Key::Int(_) => quote!(::serde::Serializer::serialize_u64(__serializer, #key)),
+            Key::Str(_) => quote!(::serde::Serializer::serialize_str(__serializer, #key)),
+        };
+        let entry = |value: TokenStream| {
+            quote! {
+                let mut __map = ::serde::Serializer::serialize_map(
+                    __serializer,
+                    ::core::option::Option::Some(1),
+                )?;
+                ::serde::ser::SerializeMap::serialize_entry(&mut __map, &#key, #value)?;
+                ::serde::ser::SerializeMap::end(__map)
+            }
+        };
+        match content {
+            Content::Unit => quote!(#ident::#name => #tag,),
+            Content::Newtype => {
+                let body = entry(quote!(__value));
+                quote!(#ident::#name(__value) => { #body })
+            }
+            Content::Struct((fields, types)) => {
+                let idents: Vec<&Ident> = fields.iter().map(|f| &f.ident).collect();
+                let map = serialize_map(fields, |f| quote!(self.#f));
+                let body = entry(quote!(&__Content { #(#idents),* }));
+                quote! {
+                    #ident::#name { #(#idents),* } => {
+                        struct __Content<'__a> {
+                            #(#idents: &'__a #types,)*
+                        }
+                        impl<'__a> ::serde::Serialize for __Content<'__a> {
+                            fn serialize<__S: ::serde::Serializer>(
+                                &self,
+                                __serializer: __S,
+                            ) -> ::core::result::Result<__S::Ok, __S::Error> {
+                                #map
+                            }
+                        }
+                        #body
+                    }
+                }
+            }
+        }
+    });
+    quote! {
+        impl ::serde::Serialize for #ident {
+            fn serialize<__S: ::serde::Serializer>(
+                &self,
+                __serializer: __S,
+            ) -> ::core::result::Result<__S::Ok, __S::Error> {
+                match self {
+                    #(#arms)*
+                }
+            }
+        }
+    }
+}
+
+/// Type deserializing keys of any format to the index of a field or variant
+fn key_type(name: &Ident, keys: &[Named]) -> TokenStream {
+    let mut ints = Vec::new();
+    let mut int_indices = Vec::new();
+    let mut strs = Vec::new();
+    let mut str_indices = Vec::new();
+    for (index, named) in keys.iter().enumerate() {
+        // Previous integer keys are aliases too.
+        for key in std::iter::once(&named.key).chain(&named.aliases) {
+            if let Key::Int(int) = key {
+                ints.push(*int);
+                int_indices.push(index);
+            }
+            strs.push(key.strings());
+            str_indices.push(index);
+        }
+    }
+    quote! {
+        struct #name(::core::option::Option<usize>);
+        impl<'de> ::serde::Deserialize<'de> for #name {
+            fn deserialize<__D: ::serde::Deserializer<'de>>(
+                __deserializer: __D,
+            ) -> ::core::result::Result<Self, __D::Error> {
+                struct __Visitor;
+                impl<'de> ::serde::de::Visitor<'de> for __Visitor {
+                    type Value = #name;
+                    fn expecting(&self, __f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
+                        __f.write_str("an integer or string key")
+                    }
+                    #[allow(unreachable_patterns)]
+                    fn visit_u64<__E: ::serde::de::Error>(
+                        self,
+                        __value: u64,
+                    ) -> ::core::result::Result<#name, __E> {
+                        ::core::result::Result::Ok(#name(match __value {
+                            #(#ints => ::core::option::Option::Some(#int_indices),)*
+                            _ => ::core::option::Option::None,
+                        }))
+                    }
+                    fn visit_i64<__E: ::serde::de::Error>(
+                        self,
+                        __value: i64,
+                    ) -> ::core::result::Result<#name, __E> {
+                        match <u64 as ::core::convert::TryFrom<i64>>::try_from(__value) {
+                            ::core::result::Result::Ok(__value) => self.visit_u64(__value),
+                            ::core::result::Result::Err(_) => {
+                                ::core::result::Result::Ok(#name(::core::option::Option::None))
+                            }
+                        }
+                    }
+                    #[allow(unreachable_patterns)]
+                    fn visit_str<__E: ::serde::de::Error>(
+                        self,
+                        __value: &str,
+                    ) -> ::core::result::Result<#name, __E> {
+                        ::core::result::Result::Ok(#name(match __value {
+                            #(#strs => ::core::option::Option::Some(#str_indices),)*
+                            _ => ::core::option::Option::None,
+                        }))
+                    }
+                    fn visit_bytes<__E: ::serde::de::Error>(
+                        self,
+                        __value: &[u8],
+                    ) -> ::core::result::Result<#name, __E> {
+                        match ::core::str::from_utf8(__value) {
+                            ::core::result::Result::Ok(__value) => self.visit_str(__value),
+                            ::core::result::Result::Err(_) => {
+                                ::core::result::Result::Ok(#name(::core::option::Option::None))
+                            }
+                        }
+                    }
+                }
+                ::serde::Deserializer::deserialize_any(__deserializer, __Visitor)
+            }
+        }
+    }
+}
+
+/// Deserialize a map of fields, missing fields are deserialized from unit, e.g. `Option` to `None`
+fn deserialize_struct(
+    ident: &Ident,
+    path: &TokenStream,
+    fields: &[Named],
+    types: &[Type],
+) -> TokenStream {
+    let key = key_type(&format_ident!("__Key"), fields);
+    let expecting = format!("struct {}", ident);
+    let idents: Vec<&Ident> = fields.iter().map(|f| &f.ident).collect();
+    let names: Vec<String> = idents.iter().map(|i| i.unraw().to_string()).collect();
+    let vars: Vec<Ident> = (0..fields.len())
+        .map(|i| format_ident!("__f{}", i))
+        .collect();
+    let indices = 0..fields.len();
+    // Missing `Option` fields are `None` like with the derive, other missing fields are errors.
+    let missing = types.iter().zip(&names).map(|(ty, name)| {
+        if is_option(ty) {
+            quote!(::core::option::Option::None)
+        } else {
+            quote! {
+                return ::core::result::Result::Err(
+                    <__A::Error as ::serde::de::Error>::missing_field(#name),
+                )
+            }
+        }
+    });
+    quote! {
+        impl<'de> ::serde::Deserialize<'de> for #ident {
+            fn deserialize<__D: ::serde::Deserializer<'de>>(
+                __deserializer: __D,
+            ) -> ::core::result::Result<Self, __D::Error> {
+                #key
+                struct __Visitor;
+                impl<'de> ::serde::de::Visitor<'de> for __Visitor {
+                    type Value = #ident;
+                    fn expecting(&self, __f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
+                        __f.write_str(#expecting)
+                    }
+                    fn visit_map<__A: ::serde::de::MapAccess<'de>>(
+                        self,
+                        mut __map: __A,
+                    ) -> ::core::result::Result<#ident, __A::Error> {
+                        #(let mut #vars: ::core::option::Option<#types> = ::core::option::Option::None;)*
+                        while let ::core::option::Option::Some(__key) =
+                            ::serde::de::MapAccess::next_key::<__Key>(&mut __map)?
+                        {
+                            match __key.0 {
+                                #(::core::option::Option::Some(#indices) => {
+                                    if #vars.is_some() {
+                                        return ::core::result::Result::Err(
+                                            <__A::Error as ::serde::de::Error>::duplicate_field(#names),
+                                        );
+                                    }
+                                    #vars = ::core::option::Option::Some(
+                                        ::serde::de::MapAccess::next_value(&mut __map)?,
+                                    );
+                                })*
+                                _ => {
+                                    ::serde::de::MapAccess::next_value::<::serde::de::IgnoredAny>(
+                                        &mut __map,
+                                    )?;
+                                }
+                            }
+                        }
+                        #(let #vars = match #vars {
+                            ::core::option::Option::Some(__value) => __value,
+                            ::core::option::Option::None => #missing,
+                        };)*
+                        ::core::result::Result::Ok(#path { #(#idents: #vars),* })
+                    }
+                }
+                ::serde::Deserializer::deserialize_map(__deserializer, __Visitor)
+            }
+        }
+    }
+}
+
+/// Whether a field is an `Option`, written as such
+fn is_option(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) if path.qself.is_none() => path
+            .path
+            .segments
+            .last()
+            .map_or(false, |segment| segment.ident == "Option"),
+        _ => false,
+    }
+}
+
+/// Deserialize a bare tag of a unit variant, or a map of a tag and the content of a variant
+fn deserialize_enum(ident: &Ident, variants: &[Named], contents: &[Content]) -> TokenStream {
+    let tag = key_type(&format_ident!("__Tag"), variants);
+    let expecting = format!("enum {}", ident);
+    let mut units = Vec::new();
+    let mut unit_indices = Vec::new();
+    let mut arms = Vec::new();
+    for (index, (variant, content)) in variants.iter().zip(contents).enumerate() {
+        let name = &variant.ident;
+        arms.push(match content {
+            Content::Unit => {
+                units.push(name);
+                unit_indices.push(index);
+                quote! {
+                    ::core::option::Option::Some(#index) => {
+                        ::serde::de::MapAccess::next_value::<()>(&mut __map)?;
+                        #ident::#name
+                    }
+                }
+            }
+            Content::Newtype => quote! {
+                ::core::option::Option::Some(#index) => {
+                    #ident::#name(::serde::de::MapAccess::next_value(&mut __map)?)
+                }
+            },
+            Content::Struct((fields, types)) => {
+                let idents: Vec<&Ident> = fields.iter().map(|f| &f.ident).collect();
+                let content = format_ident!("__Content");
+                let de = deserialize_struct(&content, &quote!(#content), fields, types);
+                quote! {
+                    ::core::option::Option::Some(#index) => {
+                        struct __Content {
+                            #(#idents: #types,)*
+                        }
+                        #de
+                        let __content: __Content = ::serde::de::MapAccess::next_value(&mut __map)?;
+                        #ident::#name { #(#idents: __content.#idents),* }
+                    }
+                }
+            }
+        });
+    }
+    quote! {
+        impl<'de> ::serde::Deserialize<'de> for #ident {
+            fn deserialize<__D: ::serde::Deserializer<'de>>(
+                __deserializer: __D,
+            ) -> ::core::result::Result<Self, __D::Error> {
+                #tag
+                fn __unit<__E: ::serde::de::Error>(
+                    __tag: __Tag,
+                ) -> ::core::result::Result<#ident, __E> {
+                    match __tag.0 {
+                        #(::core::option::Option::Some(#unit_indices) => {
+                            ::core::result::Result::Ok(#ident::#units)
+                        })*
+                        _ => ::core::result::Result::Err(__E::custom(concat!("unknown unit variant of ", #expecting))),
+                    }
+                }
+                struct __Visitor;
+                impl<'de> ::serde::de::Visitor<'de> for __Visitor {
+                    type Value = #ident;
+                    fn expecting(&self, __f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
+                        __f.write_str(#expecting)
+                    }
+                    fn visit_u64<__E: ::serde::de::Error>(
+                        self,
+                        __value: u64,
+                    ) -> ::core::result::Result<#ident, __E> {
+                        __unit(<__Tag as ::serde::Deserialize>::deserialize(
+                            ::serde::de::IntoDeserializer::<'de, __E>::into_deserializer(__value),
+                        )?)
+                    }
+                    fn visit_i64<__E: ::serde::de::Error>(
+                        self,
+                        __value: i64,
+                    ) -> ::core::result::Result<#ident, __E> {
+                        __unit(<__Tag as ::serde::Deserialize>::deserialize(
+                            ::serde::de::IntoDeserializer::<'de, __E>::into_deserializer(__value),
+                        )?)
+                    }
+                    fn visit_str<__E: ::serde::de::Error>(
+                        self,
+                        __value: &str,
+                    ) -> ::core::result::Result<#ident, __E> {
+                        __unit(<__Tag as ::serde::Deserialize>::deserialize(
+                            ::serde::de::IntoDeserializer::<'de, __E>::into_deserializer(__value),
+                        )?)
+                    }
+                    fn visit_map<__A: ::serde::de::MapAccess<'de>>(
+                        self,
+                        mut __map: __A,
+                    ) -> ::core::result::Result<#ident, __A::Error> {
+                        let __tag = match ::serde::de::MapAccess::next_key::<__Tag>(&mut __map)? {
+                            ::core::option::Option::Some(__tag) => __tag,
+                            ::core::option::Option::None => {
+                                return ::core::result::Result::Err(
+                                    <__A::Error as ::serde::de::Error>::invalid_length(0, &self),
+                                )
+                            }
+                        };
+                        let __value = match __tag.0 {
+                            #(#arms)*
+                            _ => {
+                                return ::core::result::Result::Err(
+                                    <__A::Error as ::serde::de::Error>::custom(concat!("unknown variant of ", #expecting)),
+                                )
+                            }
+                        };
+                        match ::serde::de::MapAccess::next_key::<::serde::de::IgnoredAny>(&mut __map)? {
+                            ::core::option::Option::Some(_) => ::core::result::Result::Err(
+                                <__A::Error as ::serde::de::Error>::invalid_length(2, &self),
+                            ),
+                            ::core::option::Option::None => ::core::result::Result::Ok(__value),
+                        }
+                    }
+                }
+                ::serde::Deserializer::deserialize_any(__deserializer, __Visitor)
+            }
+        }
+    }
+}
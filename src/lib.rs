@@ -37,17 +37,34 @@
 //!     assert_eq!(cs, de);
 //! }
 //! ```
+mod args;
+#[cfg(doctest)]
+mod compile_fail;
+mod integer;
+mod lock;
+mod mapping;
+mod profile;
+
+use args::{
+    ContainerArgs, FieldArgs, Format, Keys, Pools, Previous, RenameRule, Scope, SerdeNames,
+    SerdeRules, Strategy,
+};
+use lock::LockFile;
+use mapping::Mapping;
 use proc_macro::TokenStream;
+use proc_macro2::Span;
+use profile::Profile;
 use quote::quote;
 use std::collections::{HashMap, HashSet};
-use syn::parse::Parser;
+use syn::ext::IdentExt;
 use syn::{
     fold::{self, Fold},
-    parse_macro_input,
+    parse_macro_input, parse_quote,
     visit::{self, Visit},
-    Attribute, Field, Item, Variant,
+    Attribute, Error, Field, Fields, Ident, Item, ItemEnum, LitStr, Variant,
 };
 
+/// Default alphabet of names, `alphabet = "letters"`
 const ALPHABET: [char; 52] = [
     'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o', 'p', 'q', 'r', 's',
     't', 'u', 'v', 'w', 'x', 'y', 'z', 'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', 'J', 'K', 'L',
@@ -70,64 +87,1255 @@ const ALPHABET: [char; 52] = [
 /// // Serialized to: "{"a":{"b":1,"d":1,"c":1}}"
 /// //    instead of: "{"ReservationConfirmation":{"event_id":1,"user_id":1,"ticket_type":1}}"
 /// ```
+///
+/// Short names can be kept stable across schema changes with a lock file:
+/// `#[compact(lock)]` reads `compact.lock` in the crate root (or `#[compact(lock = "path")]`), keeps names of
+/// identifiers found there and appends new ones. Set `SERDE_COMPACT_LOCKED=1` to fail instead of appending, e.g. in CI.
+/// Only builds append, expansions by rust-analyzer of unsaved code leave the lock as it is.
+/// Types are locked in a section named after them, types of the same name need `lock_section = "module::Type"`.
+///
+/// A field or variant can be pinned to a short name with `#[compact(name = "x")]`.
+/// Other identifiers never get pinned names.
+///
+/// With `#[compact(strategy = "declaration")]` names are assigned in source order instead of alphabetically,
+/// so identifiers appended at the end of the type don't change existing names. Fields appended to an earlier
+/// variant come before later variants and shift their names, unless they are marked with
+/// `#[compact(since = N)]`: such identifiers are named after all identifiers of earlier versions and must be
+/// declared after them.
+/// The strategy is exposed as `Type::COMPACT_STRATEGY`.
+///
+/// `#[compact(strategy = "hash")]` derives names from a hash of each identifier, optionally salted with
+/// `hash_salt = "path::Type"`. Colliding names grow by a character up to `hash_len` (3 by default),
+/// unresolved collisions are errors. A new identifier colliding with an existing one renames both, so pin or
+/// lock names that must stay.
+///
+/// Names are made of ASCII letters, `#[compact(alphabet = "alnum")]` adds digits and `"json-safe"` all
+/// printable ASCII characters but `"` and `\`, for more single-character names. `"custom:..."` lists the
+/// characters, which must not need escaping in JSON.
+///
+/// `#[compact(format = "yaml")]` skips names YAML 1.1 reads as booleans or nulls, like `y` and `n`, or as
+/// numbers. `"xml"` and `"ron"` only generate valid element names and identifiers, `"env"` lowercase names
+/// for envy-style environment variables.
+///
+/// `#[compact(keys = "numeric")]` names identifiers "0", "1", "2"... in the order of the strategy, or with
+/// decimal hashes.
+///
+/// With `#[compact(keys = "integer")]` Serialize and Deserialize are implemented with integer field keys and
+/// variant tags instead of derived, for CBOR and MessagePack. Deserialization accepts the strings too, so
+/// JSON works as well. Only missing `Option` fields default to `None`. Types must not be generic nor have
+/// `#[serde]` attributes, and the hash strategy is not supported.
+///
+/// `#[compact(strategy = "mnemonic")]` abbreviates identifiers to the initials of their words, e.g. `ei` for
+/// `event_id`. Colliding names take more characters of the last word, then a numeric suffix.
+///
+/// `#[compact(strategy_version = 2)]` selects a revision of the naming algorithm. Version 1 is the default
+/// and never changes, version 2 doesn't skip two-character names starting with 'a'. Future improvements ship
+/// as new versions, so names don't change on upgrades.
+///
+/// Existing `#[serde(rename)]` attributes pin names, names deserialized through them or `#[serde(alias)]`
+/// are not assigned to other identifiers.
+/// A container `#[serde(rename_all)]` or `rename_all_fields` still names identifiers that are kept or not
+/// compacted, and the original names accepted with `accept_verbose`. Otherwise it is an error if all of them
+/// are compacted.
+///
+/// Renamed fields and variants keep their names with `#[compact(was = "old_ident")]`.
+///
+/// Fields and variants marked with `#[compact(keep)]`, or listed in `#[compact(keep = ["exp", "iat"])]`,
+/// keep their identifiers. Other identifiers never get their names.
+///
+/// Identifiers shorter than `#[compact(min_len = N)]` characters are kept too, with `#[compact(shorter_only)]`
+/// identifiers are kept unless their names are shorter.
+///
+/// Frequently serialized fields and variants get the shortest names with `#[compact(priority = N)]`, higher
+/// priorities are named first and `#[compact(hot)]` is the highest. With the declaration strategy priorities
+/// order names within a version.
+///
+/// `#[compact(profile = "samples/events.ndjson")]` counts identifiers in sample payloads, one verbose JSON
+/// value per line, under the names serde gives them, and names frequent identifiers first. `#[compact(export = "mappings/event.json")]` writes
+/// the names to a mapping file, e.g. to review or freeze them.
+///
+/// Only fields or only variants are compacted with `#[compact(fields)]` or `#[compact(variants)]`, the others
+/// are serialized as declared and don't take names. `#[compact(variants = false)]` leaves out variants alike.
+///
+/// Variant tags and field names of an enum never share a namespace, `#[compact(pools = "split")]` names them
+/// separately so both start at 'a'. With `pools = "per-variant"` fields of each variant are named separately too.
+///
+/// `#[compact(accept_verbose)]` adds `#[serde(alias)]` with the original identifiers, so data serialized
+/// before compacting can still be deserialized. Original names are not given to other identifiers.
+///
+/// Data serialized with previous mappings is deserialized with `#[compact(previous = "mappings/v1.json")]`,
+/// a list of such files or an inline `previous(event_id = "c", user_id = "d")`. Identifiers whose names
+/// changed get `#[serde(alias)]` with the previous names, which must not be names of other identifiers.
+///
+/// `Type::COMPACT_FINGERPRINT` is a hash of identifiers and their names. Pin it with
+/// `#[compact(fingerprint = 0x...)]` to fail the build whenever names change.
+///
+/// Names of removed fields and variants can be kept from being reassigned with
+/// `#[compact(reserved = ["c", "d"])]` or, except for the declaration strategy, `#[compact(reserved_idents = ["old_field"])]`.
+/// Unless pools are shared, reserved identifiers name their pool, e.g. `"variants:OldVariant"`, `"fields:old_field"`
+/// or `"Variant.old_field"`.
 #[proc_macro_attribute]
-pub fn compact(_attr: TokenStream, item: TokenStream) -> TokenStream {
+pub fn compact(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(attr as ContainerArgs);
     let input = parse_macro_input!(item as Item);
+    match expand(args, input) {
+        Ok(output) => TokenStream::from(output),
+        Err(err) => TokenStream::from(err.to_compile_error()),
+    }
+}
+
+fn expand(args: ContainerArgs, input: Item) -> syn::Result<proc_macro2::TokenStream> {
+    if args.keys == Keys::Integer {
+        integer::check(&input)?;
+    }
+    if let (Item::Struct(_), false) = (&input, args.scope.fields) {
+        return Err(Error::new(
+            Span::call_site(),
+            "compacting only variants requires an enum",
+        ));
+    }
+    let pools = match (args.pools, &input) {
+        (Pools::Shared, _) => vec![Pool::All],
+        (Pools::Split, Item::Enum(_)) => vec![Pool::Variants, Pool::Fields],
+        (Pools::PerVariant, Item::Enum(item)) => std::iter::once(Pool::Variants)
+            .chain(
+                item.variants
+                    .iter()
+                    .map(|variant| Pool::VariantFields(variant.ident.to_string())),
+            )
+            .collect(),
+        (pools, _) => {
+            return Err(Error::new(
+                Span::call_site(),
+                format!("`pools = \"{}\"` requires an enum", pools.as_str()),
+            ))
+        }
+    };
+
+    let profile = match &args.profile {
+        Some(path) => Some(Profile::read(path)?),
+        None => None,
+    };
+
+    // Names of kept identifiers follow `#[serde(rename_all)]`.
+    let serde_rules = match &input {
+        Item::Struct(item) => {
+            let rules = SerdeRules::from_attrs(&item.attrs);
+            vec![("fields", rules.rename_all)]
+        }
+        Item::Enum(item) => {
+            let rules = SerdeRules::from_attrs(&item.attrs);
+            vec![
+                ("variants", rules.rename_all),
+                ("fields", rules.rename_all_fields),
+            ]
+        }
+        _ => Vec::new(),
+    };
+    let rules: HashMap<&'static str, RenameRule> = serde_rules
+        .iter()
+        .filter_map(|(kind, rename_all)| Some((*kind, rename_all.as_ref()?.rule?)))
+        .collect();
+
+    // Collect field names and tags of each pool.
+    let mut collectors = Vec::new();
+    let mut error = None;
+    for pool in pools {
+        let mut collector = NameCollector {
+            scope: args.scope,
+            pool,
+            rules: rules.clone(),
+            ..NameCollector::default()
+        };
+        collector.visit_item(&input);
+        if let Some(profile) = &profile {
+            collector.frequency = collector
+                .order
+                .iter()
+                .map(|name| {
+                    // Samples are verbose payloads, count the names serde gave the identifier.
+                    let count = collector
+                        .verbose_names(name)
+                        .iter()
+                        .filter_map(|verbose| profile.counts.get(verbose))
+                        .sum();
+                    (name.clone(), count)
+                })
+                .collect();
+        }
+        if let Some(err) = collector.error.take() {
+            combine(&mut error, err);
+        }
+        collectors.push(collector);
+    }
+    if let Some(err) = error {
+        return Err(err);
+    }
+    let since = collectors.iter().find_map(|c| c.since_used.as_ref());
+    if let (Some(since), false) = (since, args.strategy == Strategy::Declaration) {
+        return Err(Error::new(
+            since.span(),
+            "`since` requires `#[compact(strategy = \"declaration\")]`",
+        ));
+    }
+    let priority = collectors.iter().find_map(|c| c.priority_used.as_ref());
+    if let (Some(priority), Strategy::Hash) = (priority, args.strategy) {
+        return Err(Error::new(
+            priority.span(),
+            format!("`{}` doesn't apply to `strategy = \"hash\"`", priority),
+        ));
+    }
+    for ident in &args.keep {
+        if collectors
+            .iter()
+            .all(|c| c.listed(&ident.value()).is_none())
+        {
+            return Err(Error::new(
+                ident.span(),
+                format!("`{}` is not declared", ident.value()),
+            ));
+        }
+    }
+    for ident in &args.reserved_idents {
+        let entry = ident.value();
+        let reserved = collectors
+            .iter()
+            .find_map(|c| Some((c, c.pool.reserved(&entry)?)));
+        match reserved {
+            Some((collector, name)) if collector.names.contains(&name) => {
+                return Err(Error::new(
+                    ident.span(),
+                    format!("`{}` is reserved but still declared", entry),
+                ));
+            }
+            Some(_) => {}
+            None => {
+                let expected = match args.pools {
+                    Pools::Shared => "an identifier, e.g. \"old_field\"",
+                    Pools::Split => "\"variants:OldVariant\" or \"fields:old_field\"",
+                    Pools::PerVariant => "\"variants:OldVariant\" or \"Variant.old_field\"",
+                };
+                return Err(Error::new(
+                    ident.span(),
+                    format!(
+                        "`{}` names no pool of this type, expected {}",
+                        entry, expected
+                    ),
+                ));
+            }
+        }
+    }
+
+    let previous = args
+        .previous
+        .iter()
+        .map(|previous| match previous {
+            Previous::File(path) => Mapping::read(path),
+            Previous::Inline(mapping) => Ok(mapping.clone()),
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+    let mut lock = match &args.lock {
+        Some(path) => Some(LockFile::open(path)?),
+        None => None,
+    };
+    let section = match (&input, &args.lock_section) {
+        (Item::Struct(_) | Item::Enum(_), Some(section)) => Some(section.value()),
+        (Item::Struct(item), None) => Some(item.ident.to_string()),
+        (Item::Enum(item), None) => Some(item.ident.to_string()),
+        _ => None,
+    };
+
+    // Map each pool.
+    let mut mappers = Vec::new();
+    match (&mut lock, &section) {
+        (Some(lock), Some(ty)) => {
+            let version = args.version.to_string();
+            lock.settle(ty, "pools", args.pools.as_str(), Pools::Shared.as_str())?;
+            for collector in &collectors {
+                let section = collector.pool.section(ty);
+                lock.settle(
+                    &section,
+                    "strategy",
+                    args.strategy.as_str(),
+                    Strategy::Alphabetical.as_str(),
+                )?;
+                lock.settle(&section, "version", &version, "1")?;
+                lock.settle(&section, "alphabet", &args.alphabet.name, "letters")?;
+                lock.settle(
+                    &section,
+                    "format",
+                    args.format.as_str(),
+                    Format::Json.as_str(),
+                )?;
+                lock.settle(&section, "keys", args.keys.as_str(), Keys::Strings.as_str())?;
+                let mapper = NameMapper::new(collector, &args, &previous, lock.entries(&section))?;
+                lock.append(&section, mapper.assigned.clone())?;
+                mappers.push(mapper);
+            }
+        }
+        (Some(lock), None) => {
+            return Err(lock.error("only structs and enums can be locked".to_string()))
+        }
+        (None, _) => {
+            for collector in &collectors {
+                mappers.push(NameMapper::new(collector, &args, &previous, &[])?);
+            }
+        }
+    }
+
+    // Explicit renames override `#[serde(rename_all)]`, it must apply to some identifier or to the
+    // original names accepted with `accept_verbose`.
+    for (kind, rename_all) in &serde_rules {
+        let rename_all = match rename_all {
+            Some(rename_all) => rename_all,
+            None => continue,
+        };
+        let in_scope = match *kind {
+            "variants" => args.scope.variants,
+            _ => args.scope.fields,
+        };
+        let kept: Vec<bool> = collectors
+            .iter()
+            .zip(&mappers)
+            .flat_map(|(collector, mapper)| {
+                collector
+                    .kinds
+                    .iter()
+                    .filter(|(_, k)| *k == kind)
+                    .map(move |(name, _)| mapper.kept.contains(name))
+            })
+            .collect();
+        if in_scope && !args.accept_verbose && !kept.is_empty() && !kept.contains(&true) {
+            return Err(Error::new(
+                rename_all.key.span(),
+                format!(
+                    "`{}` has no effect, all {} are compacted",
+                    rename_all.key, kind
+                ),
+            ));
+        }
+    }
 
-    // Collect field names and tags.
-    let mut collector = NameCollector {
-        names: HashSet::new(),
+    // Guard against accidental changes of names.
+    let mut table: Vec<(String, String)> = collectors
+        .iter()
+        .zip(&mappers)
+        .flat_map(|(collector, mapper)| {
+            mapper
+                .table(collector)
+                .into_iter()
+                .map(move |(name, key)| (collector.pool.qualify(name), key))
+        })
+        .collect();
+    table.sort();
+    let fingerprint = fingerprint(&table);
+    if let Some(expected) = &args.fingerprint {
+        if expected.base10_parse::<u64>()? != fingerprint {
+            return Err(Error::new(
+                expected.span(),
+                format!(
+                    "names changed, fingerprint is {:#018x}: {}",
+                    fingerprint,
+                    describe_changes(&table, previous.last())
+                ),
+            ));
+        }
+    }
+    let mut renamer = Renamer {
+        pools: collectors
+            .into_iter()
+            .map(|c| c.pool)
+            .zip(mappers)
+            .collect(),
+        scope: args.scope,
+        variant: None,
+    };
+    let output = renamer.fold_item(input);
+    let (output, impls) = match args.keys {
+        Keys::Integer => {
+            let (output, impls) = integer::expand(output)?;
+            (output, Some(impls))
+        }
+        _ => (output, None),
     };
-    collector.visit_item(&input);
 
-    // Map.
-    let mut mapper = NameMapper::new(collector.names);
-    let output = mapper.fold_item(input);
-    TokenStream::from(quote!(#output))
+    // Write files last, a failing build must not append to the lock.
+    if let Some(lock) = &lock {
+        lock.save()?;
+    }
+    if let Some(path) = &args.export {
+        Mapping::write(path, &table)?;
+    }
+
+    // Record the strategy, so that a type can't be compacted twice.
+    let consts = match &output {
+        Item::Struct(item) => Some((&item.ident, &item.generics)),
+        Item::Enum(item) => Some((&item.ident, &item.generics)),
+        _ => None,
+    }
+    .map(|(ident, generics)| {
+        let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+        let strategy = args.strategy.as_str();
+        quote! {
+            #[allow(dead_code)]
+            impl #impl_generics #ident #ty_generics #where_clause {
+                #[doc(hidden)]
+                pub const COMPACT_STRATEGY: &'static str = #strategy;
+                /// Hash of identifiers and their names
+                pub const COMPACT_FINGERPRINT: u64 = #fingerprint;
+            }
+        }
+    });
+
+    // Rebuild when the lock or mapping files change, rust-analyzer doesn't create the lock.
+    let tracked = lock
+        .as_ref()
+        .map(|lock| lock.path())
+        .filter(|path| path.exists())
+        .into_iter()
+        .chain(
+            previous
+                .iter()
+                .filter_map(|mapping| mapping.path.as_deref()),
+        )
+        .chain(profile.as_ref().map(|profile| profile.path.as_path()))
+        .map(|path| {
+            let path = path.display().to_string();
+            quote!(
+                const _: &[u8] = include_bytes!(#path);
+            )
+        });
+    Ok(quote!(#output #impls #consts #(#tracked)*))
+}
+
+/// Identifiers named together
+#[derive(Clone, PartialEq, Eq, Debug)]
+enum Pool {
+    /// Fields and variants
+    All,
+    Variants,
+    Fields,
+    /// Fields of a variant
+    VariantFields(String),
+}
+
+impl Pool {
+    fn contains(&self, field: bool, variant: Option<&str>) -> bool {
+        match self {
+            Pool::All => true,
+            Pool::Variants => !field,
+            Pool::Fields => field,
+            Pool::VariantFields(name) => field && variant == Some(name.as_str()),
+        }
+    }
+
+    /// Name of an identifier in the table of a type
+    fn qualify(&self, name: String) -> String {
+        match self {
+            Pool::VariantFields(variant) => format!("{}.{}", variant, name),
+            _ => name,
+        }
+    }
+
+    /// Identifier of a `reserved_idents` entry of the pool: bare in the shared pool, `variants:` or
+    /// `fields:` in split pools and `Variant.field` for fields of a variant.
+    fn reserved(&self, entry: &str) -> Option<String> {
+        let name = match (self, entry.split_once(':'), entry.split_once('.')) {
+            (Pool::All, None, None) => entry,
+            (Pool::Variants, Some(("variants", name)), _) => name,
+            (Pool::Fields, Some(("fields", name)), _) => name,
+            (Pool::VariantFields(variant), None, Some((of, name))) if of == variant => name,
+            _ => return None,
+        };
+        Some(name.to_string())
+    }
+
+    /// Lock file section of the pool
+    fn section(&self, ty: &str) -> String {
+        match self {
+            Pool::All => ty.to_string(),
+            Pool::Variants => format!("{}/variants", ty),
+            Pool::Fields => format!("{}/fields", ty),
+            Pool::VariantFields(variant) => format!("{}::{}", ty, variant),
+        }
+    }
 }
 
-/// Collect all names before mapping
+/// Collect all names of a pool before mapping
 struct NameCollector {
+    /// Kinds of identifiers to compact
+    scope: Scope,
+    pool: Pool,
+    /// Variant of the fields being visited
+    variant: Option<String>,
+    names: HashSet<String>,
+    /// Names in order of first declaration
+    order: Vec<String>,
+    /// Span of the first declaration
+    spans: HashMap<String, Span>,
+    /// Names pinned with `#[compact(name = "")]`
+    pins: HashMap<String, LitStr>,
+    /// Identifiers kept with `#[compact(keep)]`
+    kept: HashMap<String, Span>,
+    /// Current identifiers of names renamed with `#[compact(was = "")]`
+    renamed: HashMap<String, Vec<String>>,
+    /// Fields or variants, by name
+    kinds: HashMap<String, &'static str>,
+    /// Rules of `#[serde(rename_all)]` for names of each kind
+    rules: HashMap<&'static str, RenameRule>,
+    /// Other names deserialized by `#[serde(rename)]` and `#[serde(alias)]`
+    serde_keys: HashMap<String, Vec<String>>,
+    /// Earliest `#[compact(since = N)]` of a name
+    since: HashMap<String, u32>,
+    /// Highest `#[compact(priority = N)]` of a name
+    priority: HashMap<String, u32>,
+    /// Occurrences of a name in the profile
+    frequency: HashMap<String, u64>,
+    /// Enclosing lists of fields or variants
+    siblings: Vec<Siblings>,
+    since_used: Option<Ident>,
+    priority_used: Option<Ident>,
+    error: Option<Error>,
+}
+
+/// Fields of a struct or variant, or variants of an enum
+#[derive(Default)]
+struct Siblings {
+    /// Latest version seen
+    version: u32,
     names: HashSet<String>,
 }
 
+impl Default for NameCollector {
+    fn default() -> Self {
+        Self {
+            scope: Scope {
+                fields: true,
+                variants: true,
+            },
+            pool: Pool::All,
+            variant: None,
+            names: HashSet::new(),
+            order: Vec::new(),
+            spans: HashMap::new(),
+            pins: HashMap::new(),
+            kept: HashMap::new(),
+            renamed: HashMap::new(),
+            kinds: HashMap::new(),
+            rules: HashMap::new(),
+            serde_keys: HashMap::new(),
+            since: HashMap::new(),
+            priority: HashMap::new(),
+            frequency: HashMap::new(),
+            siblings: Vec::new(),
+            since_used: None,
+            priority_used: None,
+            error: None,
+        }
+    }
+}
+
+impl NameCollector {
+    fn add(&mut self, ident: &Ident, attrs: &[Attribute], kind: &'static str, in_scope: bool) {
+        if !in_scope {
+            // Out of scope identifiers don't take names, arguments would have no effect.
+            if let Some(attr) = attrs.iter().find(|attr| args::is_compact(attr)) {
+                let err =
+                    Error::new_spanned(attr, format!("{} of this type are not compacted", kind));
+                combine(&mut self.error, err);
+            }
+            return;
+        }
+        let mut args = match FieldArgs::from_attrs(attrs) {
+            Ok(args) => args,
+            Err(err) => return combine(&mut self.error, err),
+        };
+        let name = args.ident(ident);
+        if let Err(err) = self.serde_names(ident, &name, attrs, &mut args) {
+            return combine(&mut self.error, err);
+        }
+        if let Err(err) = self.apply(ident, &name, args) {
+            combine(&mut self.error, err);
+        }
+        self.kinds.insert(name.clone(), kind);
+        if self.names.insert(name.clone()) {
+            self.spans.insert(name.clone(), ident.span());
+            self.order.push(name);
+        }
+    }
+
+    /// Names given with `#[serde]` are kept: a rename pins the serialized name.
+    fn serde_names(
+        &mut self,
+        ident: &Ident,
+        name: &str,
+        attrs: &[Attribute],
+        args: &mut FieldArgs,
+    ) -> syn::Result<()> {
+        let serde = SerdeNames::from_attrs(attrs)?;
+        let mut keys: Vec<String> = serde.aliases.iter().map(LitStr::value).collect();
+        if serde.renamed() {
+            let conflict = match (&args.name, &args.keep) {
+                (Some(lit), _) => Some(lit.span()),
+                (None, Some(keep)) => Some(keep.span()),
+                (None, None) => None,
+            };
+            if let Some(span) = conflict {
+                return Err(Error::new(
+                    span,
+                    format!("`{}` is already renamed with `#[serde(rename)]`", name),
+                ));
+            }
+            let declared = LitStr::new(&ident.unraw().to_string(), ident.span());
+            let serialize = serde.serialize.unwrap_or_else(|| declared.clone());
+            let deserialize = serde.deserialize.unwrap_or(declared);
+            if deserialize.value() != serialize.value() {
+                keys.push(deserialize.value());
+            }
+            args.name = Some(serialize);
+        }
+        if !keys.is_empty() {
+            self.serde_keys
+                .entry(name.to_string())
+                .or_default()
+                .extend(keys);
+        }
+        Ok(())
+    }
+
+    fn apply(&mut self, ident: &Ident, name: &str, args: FieldArgs) -> syn::Result<()> {
+        if let Some(was) = &args.was {
+            let current = ident.unraw().to_string();
+            if current == was.value() {
+                return Err(Error::new(was.span(), "identifier wasn't renamed"));
+            }
+            let renamed = self.renamed.entry(name.to_string()).or_default();
+            if !renamed.contains(&current) {
+                renamed.push(current);
+            }
+        }
+        let version = match &args.since {
+            Some((version, key)) => {
+                self.since_used.get_or_insert_with(|| key.clone());
+                *version
+            }
+            None => 0,
+        };
+        if let Some(siblings) = self.siblings.last_mut() {
+            if !siblings.names.insert(name.to_string()) {
+                return Err(Error::new(
+                    ident.span(),
+                    format!("`{}` is already declared", name),
+                ));
+            }
+            if version < siblings.version {
+                return Err(Error::new(
+                    ident.span(),
+                    format!(
+                        "`{}` of version {} is declared after identifiers of version {}, new identifiers must be appended",
+                        name, version, siblings.version
+                    ),
+                ));
+            }
+            siblings.version = version;
+        }
+        if let Some(keep) = &args.keep {
+            self.kept.insert(name.to_string(), keep.span());
+        }
+        let since = self.since.entry(name.to_string()).or_insert(version);
+        *since = (*since).min(version);
+        if let Some((priority, key)) = &args.priority {
+            self.priority_used.get_or_insert_with(|| key.clone());
+            let max = self.priority.entry(name.to_string()).or_default();
+            *max = (*max).max(*priority);
+        }
+        self.pin(name, args)
+    }
+
+    fn pin(&mut self, name: &str, args: FieldArgs) -> syn::Result<()> {
+        let pin = match args.name {
+            Some(pin) => pin,
+            None => return Ok(()),
+        };
+        if let Some(other) = self.pins.get(name) {
+            if other.value() != pin.value() {
+                return Err(Error::new(
+                    pin.span(),
+                    format!("`{}` is already pinned to \"{}\"", name, other.value()),
+                ));
+            }
+        }
+        if let Some((other, _)) = self
+            .pins
+            .iter()
+            .find(|(other, key)| *other != name && key.value() == pin.value())
+        {
+            return Err(Error::new(
+                pin.span(),
+                format!("\"{}\" is already pinned to `{}`", pin.value(), other),
+            ));
+        }
+        self.pins.insert(name.to_string(), pin);
+        Ok(())
+    }
+}
+
+impl NameCollector {
+    /// Name of an identifier listed in the container arguments, raw ones may be listed without `r#`
+    fn listed(&self, ident: &str) -> Option<String> {
+        [ident.to_string(), format!("r#{}", ident)]
+            .into_iter()
+            .find(|name| self.names.contains(name))
+    }
+
+    /// Identifiers a name was serialized with before compacting
+    fn verbose_names(&self, name: &str) -> Vec<String> {
+        let mut names = vec![self.serialized(name, name)];
+        if let Some(renamed) = self.renamed.get(name) {
+            names.extend(renamed.iter().map(|ident| self.serialized(name, ident)));
+        }
+        names
+    }
+
+    /// Name serde gives an identifier without `#[serde(rename)]`
+    fn serialized(&self, name: &str, ident: &str) -> String {
+        let kind = self.kinds.get(name).copied();
+        match (kind, kind.and_then(|kind| self.rules.get(kind))) {
+            (Some("variants"), Some(rule)) => rule.apply_to_variant(unraw(ident)),
+            (_, Some(rule)) => rule.apply_to_field(unraw(ident)),
+            (_, None) => unraw(ident).to_string(),
+        }
+    }
+}
+
 impl<'ast> Visit<'ast> for NameCollector {
+    fn visit_fields(&mut self, node: &'ast Fields) {
+        self.siblings.push(Siblings::default());
+        visit::visit_fields(self, node);
+        self.siblings.pop();
+    }
+    fn visit_item_enum(&mut self, node: &'ast ItemEnum) {
+        self.siblings.push(Siblings::default());
+        visit::visit_item_enum(self, node);
+        self.siblings.pop();
+    }
     fn visit_field(&mut self, node: &'ast Field) {
-        if let Some(ident) = &node.ident {
-            self.names.insert(ident.to_string());
+        match &node.ident {
+            Some(ident) if self.pool.contains(true, self.variant.as_deref()) => {
+                self.add(ident, &node.attrs, "fields", self.scope.fields)
+            }
+            _ => {}
         }
         visit::visit_field(self, node);
     }
     fn visit_variant(&mut self, node: &'ast Variant) {
-        self.names.insert(node.ident.to_string());
+        if self.pool.contains(false, None) {
+            self.add(&node.ident, &node.attrs, "variants", self.scope.variants);
+        }
+        self.variant = Some(node.ident.to_string());
         visit::visit_variant(self, node);
+        self.variant = None;
     }
 }
 
-/// Sort collected names and insert map macros
+/// Sort collected names of a pool and assign short names
 struct NameMapper {
     map: HashMap<String, String>,
+    /// Names assigned in addition to the locked ones
+    assigned: Vec<(String, String)>,
+    /// Names to deserialize in addition to the short ones
+    aliases: HashMap<String, Vec<String>>,
+    /// Identifiers serialized as declared
+    kept: HashSet<String>,
 }
 
 impl NameMapper {
-    fn new(names: HashSet<String>) -> Self {
-        let mut sorted_names: Vec<String> = names.into_iter().collect();
-        sorted_names.sort();
+    /// Name the identifiers of a pool with the strategy, identifiers shorter than `min_len` are kept
+    fn new(
+        collector: &NameCollector,
+        args: &ContainerArgs,
+        previous: &[Mapping],
+        locked: &[(String, String)],
+    ) -> syn::Result<Self> {
+        let renamed = |name: &String| {
+            !collector.pins.contains_key(name) && !locked.iter().any(|(locked, _)| locked == name)
+        };
+        let mut short: Vec<String> = collector
+            .order
+            .iter()
+            .filter(|name| unraw(name).chars().count() < args.min_len && renamed(name))
+            .cloned()
+            .collect();
+        // With `shorter_only` identifiers are kept as declared if they wouldn't get shorter names.
+        loop {
+            let mapper = Self::assign(collector, args, previous, locked, &short)?;
+            if !args.shorter_only {
+                return Ok(mapper);
+            }
+            // Keeping an identifier frees its name, check again with the next names.
+            let longer: Vec<String> = mapper
+                .assigned
+                .iter()
+                .filter(|(name, key)| {
+                    collector.names.contains(name)
+                        && renamed(name)
+                        && key.chars().count() >= unraw(name).chars().count()
+                })
+                .map(|(name, _)| name.clone())
+                .collect();
+            if longer.is_empty() {
+                return Ok(mapper);
+            }
+            short.extend(longer);
+        }
+    }
+
+    /// Pinned and locked names keep their keys, the rest are named by the strategy with keys not taken by them.
+    fn assign(
+        collector: &NameCollector,
+        args: &ContainerArgs,
+        previous: &[Mapping],
+        locked: &[(String, String)],
+        short: &[String],
+    ) -> syn::Result<Self> {
+        // Removed identifiers keep taking their names.
+        let mut names: Vec<String> = collector.order.clone();
+        let mut spans = collector.spans.clone();
+        for ident in &args.reserved_idents {
+            // Reserved in another pool.
+            let name = match collector.pool.reserved(&ident.value()) {
+                Some(name) => name,
+                None => continue,
+            };
+            spans.insert(name.clone(), ident.span());
+            names.push(name);
+        }
+        let reserved: HashMap<String, &LitStr> =
+            args.reserved.iter().map(|lit| (lit.value(), lit)).collect();
+
         let mut map: HashMap<String, String> = HashMap::new();
-        for (idx, name) in sorted_names.into_iter().enumerate() {
-            map.insert(name, Self::get_name(idx));
+        let mut taken: HashSet<String> = reserved.keys().cloned().collect();
+        taken.extend(collector.serde_keys.values().flatten().cloned());
+        // Original names read with `accept_verbose` must not be names of other identifiers.
+        if args.accept_verbose {
+            for name in &collector.order {
+                taken.extend(collector.verbose_names(name));
+            }
+        }
+
+        // Kept identifiers are serialized as declared, no name may collide with them.
+        let mut kept: Vec<(String, Span)> = collector
+            .kept
+            .iter()
+            .map(|(name, span)| (name.clone(), *span))
+            .collect();
+        for ident in &args.keep {
+            let name = match collector.listed(&ident.value()) {
+                Some(name) => name,
+                // Declared in another pool.
+                None => continue,
+            };
+            if collector.pins.contains_key(&name) {
+                return Err(Error::new(
+                    ident.span(),
+                    format!("`{}` is kept but pinned", name),
+                ));
+            }
+            kept.push((name, ident.span()));
+        }
+        kept.extend(
+            short
+                .iter()
+                .map(|name| (name.clone(), collector.spans[name])),
+        );
+        kept.sort_by(|a, b| a.0.cmp(&b.0));
+        kept.dedup_by(|a, b| a.0 == b.0);
+        for (name, span) in &kept {
+            let key = collector.serialized(name, name);
+            let collision = collector
+                .pins
+                .iter()
+                .find(|(_, pin)| pin.value() == key)
+                .map(|(other, _)| other)
+                .or_else(|| {
+                    locked
+                        .iter()
+                        .find(|(_, locked)| *locked == key)
+                        .map(|(other, _)| other)
+                })
+                .filter(|other| *other != name);
+            if let Some(other) = collision {
+                return Err(Error::new(
+                    *span,
+                    format!("`{}` is kept but also the name of `{}`", name, other),
+                ));
+            }
+            if reserved.contains_key(&key) {
+                return Err(Error::new(
+                    *span,
+                    format!("`{}` is kept but reserved", name),
+                ));
+            }
+            map.insert(name.clone(), key.clone());
+            taken.insert(key);
+        }
+
+        for (name, key) in locked {
+            taken.insert(key.clone());
+            if names.contains(name) && !map.contains_key(name) {
+                if let Some(lit) = reserved.get(key) {
+                    return Err(Error::new(
+                        lit.span(),
+                        format!("\"{}\" is reserved but locked to `{}`", key, name),
+                    ));
+                }
+                map.insert(name.clone(), key.clone());
+            }
+        }
+        let mut assigned = Vec::new();
+        let mut pins: Vec<(&String, &LitStr)> = collector.pins.iter().collect();
+        pins.sort_by_key(|(name, _)| *name);
+        for (name, pin) in pins {
+            let pin_value = pin.value();
+            if !args.format.allows(&pin_value) {
+                return Err(Error::new(
+                    pin.span(),
+                    format!(
+                        "\"{}\" is misread or rejected by format = \"{}\"",
+                        pin_value,
+                        args.format.as_str()
+                    ),
+                ));
+            }
+            if reserved.contains_key(&pin_value) {
+                return Err(Error::new(
+                    pin.span(),
+                    format!("\"{}\" is reserved", pin_value),
+                ));
+            }
+            if let Some((other, key)) = locked
+                .iter()
+                .find(|(other, key)| (other == name) != (*key == pin_value))
+            {
+                return Err(Error::new(
+                    pin.span(),
+                    format!("lock file assigns \"{}\" to `{}`", key, other),
+                ));
+            }
+            if !map.contains_key(name) {
+                map.insert(name.clone(), pin_value.clone());
+                assigned.push((name.clone(), pin_value.clone()));
+            }
+            taken.insert(pin_value);
+        }
+        let mut sorted_names: Vec<&String> = names
+            .iter()
+            .filter(|name| !map.contains_key(*name))
+            .collect();
+        // Higher priorities, then frequent names are named first, stable sorts keep the order of the
+        // strategy otherwise.
+        let priority = |name: &String| {
+            std::cmp::Reverse((
+                collector.priority.get(name).copied().unwrap_or(0),
+                collector.frequency.get(name).copied().unwrap_or(0),
+            ))
+        };
+        let named = match args.strategy {
+            Strategy::Alphabetical => {
+                sorted_names.sort();
+                sorted_names.sort_by_key(|name| priority(name));
+                Self::index_names(sorted_names, &taken, args)
+            }
+            Strategy::Declaration => {
+                // Stable sort keeps declaration order within a version.
+                sorted_names.sort_by_key(|name| (collector.since[*name], priority(name)));
+                Self::index_names(sorted_names, &taken, args)
+            }
+            Strategy::Hash => {
+                sorted_names.sort();
+                Self::hash_names(&spans, sorted_names, &taken, args)?
+            }
+            Strategy::Mnemonic => {
+                sorted_names.sort();
+                sorted_names.sort_by_key(|name| priority(name));
+                Self::mnemonic_names(&spans, sorted_names, &taken, args.format)?
+            }
+        };
+        for (name, key) in named {
+            map.insert(name.clone(), key.clone());
+            assigned.push((name, key));
+        }
+
+        // Aliases must not be mistaken for names of other identifiers.
+        let mut claims: HashMap<String, &String> =
+            map.iter().map(|(name, key)| (key.clone(), name)).collect();
+        for name in &collector.order {
+            for key in collector.serde_keys.get(name).into_iter().flatten() {
+                match claims.get(key) {
+                    Some(other) if *other != name => {
+                        return Err(Error::new(
+                            spans[name],
+                            format!(
+                                "\"{}\" is deserialized as `{}` but is the name of `{}`",
+                                key, name, other
+                            ),
+                        ))
+                    }
+                    _ => {
+                        claims.insert(key.clone(), name);
+                    }
+                }
+            }
+        }
+        let mut aliases: HashMap<String, Vec<String>> = HashMap::new();
+        for name in &collector.order {
+            let mut candidates = Vec::new();
+            if args.accept_verbose {
+                for verbose in collector.verbose_names(name) {
+                    candidates.push((verbose, spans[name]));
+                }
+            }
+            for mapping in previous {
+                // Exported per-variant tables qualify fields with their variant.
+                let qualified = collector.pool.qualify(name.clone());
+                if let Some(key) = mapping.get(&qualified).or_else(|| mapping.get(name)) {
+                    candidates.push((key.to_string(), mapping.span));
+                }
+            }
+            for (alias, span) in candidates {
+                match claims.get(&alias) {
+                    Some(other) if *other != name => {
+                        return Err(Error::new(
+                            span,
+                            format!(
+                                "alias \"{}\" of `{}` is ambiguous with the name of `{}`",
+                                alias, name, other
+                            ),
+                        ))
+                    }
+                    Some(_) => {}
+                    None => {
+                        claims.insert(alias.clone(), name);
+                        aliases.entry(name.clone()).or_default().push(alias);
+                    }
+                }
+            }
         }
-        Self { map }
+
+        Ok(Self {
+            map,
+            assigned,
+            aliases,
+            kept: kept.into_iter().map(|(name, _)| name).collect(),
+        })
+    }
+
+    /// Sorted identifiers and names of a type
+    fn table(&self, collector: &NameCollector) -> Vec<(String, String)> {
+        let mut table: Vec<(String, String)> = collector
+            .order
+            .iter()
+            .map(|name| (name.clone(), self.map[name].clone()))
+            .collect();
+        table.sort();
+        table
+    }
+
+    /// Assign names in order, skipping taken ones and those the format would misread
+    fn index_names(
+        names: Vec<&String>,
+        taken: &HashSet<String>,
+        args: &ContainerArgs,
+    ) -> Vec<(String, String)> {
+        let alphabet = args.chars();
+        let mut idx = 0;
+        names
+            .into_iter()
+            .map(|name| {
+                let key = loop {
+                    let key = match (args.keys, args.version) {
+                        (Keys::Numeric | Keys::Integer, _) | (_, 1) => {
+                            Self::get_name(idx, alphabet)
+                        }
+                        _ => Self::get_bijective_name(idx, alphabet),
+                    };
+                    idx += 1;
+                    if !taken.contains(&key) && args.format.allows(&key) {
+                        break key;
+                    }
+                };
+                (name.clone(), key)
+            })
+            .collect()
+    }
+
+    /// Hash names starting with a single character. All names colliding at a length move on to the next one,
+    /// so a name only depends on names it collides with.
+    fn hash_names(
+        spans: &HashMap<String, Span>,
+        mut names: Vec<&String>,
+        taken: &HashSet<String>,
+        args: &ContainerArgs,
+    ) -> syn::Result<Vec<(String, String)>> {
+        let (alphabet, max_len) = (args.chars(), args.hash_len);
+        let salt = args.hash_salt.as_ref().map(|salt| salt.value());
+        let hash = |name: &str| match &salt {
+            Some(salt) => fnv1a(format!("{}::{}", salt, unraw(name)).as_bytes()),
+            None => fnv1a(unraw(name).as_bytes()),
+        };
+        let mut named = Vec::new();
+        for len in 1..=max_len {
+            let keys: Vec<(&String, String)> = names
+                .iter()
+                .map(|name| (*name, Self::get_hash_name(hash(name), len, alphabet)))
+                .collect();
+            let mut counts: HashMap<&str, usize> = HashMap::new();
+            for (_, key) in &keys {
+                *counts.entry(key).or_default() += 1;
+            }
+            names.clear();
+            for (name, key) in &keys {
+                if counts[key.as_str()] == 1 && !taken.contains(key) && args.format.allows(key) {
+                    named.push((name.to_string(), key.clone()));
+                } else {
+                    names.push(name);
+                }
+            }
+        }
+        let mut error = None;
+        for name in names {
+            combine(
+                &mut error,
+                Error::new(
+                    spans[name],
+                    format!(
+                        "hash of `{}` collides within hash_len = {}, pin it with #[compact(name = \"\")]",
+                        name, max_len
+                    ),
+                ),
+            );
+        }
+        match error {
+            Some(error) => Err(error),
+            None => Ok(named),
+        }
+    }
+
+    /// Abbreviate names in order, earlier names get the shorter abbreviations on collisions.
+    /// Names without a free abbreviation get a numeric suffix, e.g. `tt2`.
+    fn mnemonic_names(
+        spans: &HashMap<String, Span>,
+        names: Vec<&String>,
+        taken: &HashSet<String>,
+        format: Format,
+    ) -> syn::Result<Vec<(String, String)>> {
+        let mut taken = taken.clone();
+        let mut named = Vec::new();
+        let mut error = None;
+        for name in names {
+            let mut candidates = Self::get_mnemonics(unraw(name));
+            if format == Format::Env {
+                candidates = candidates.iter().map(|key| key.to_lowercase()).collect();
+            }
+            // One of the suffixes is free unless the format rejects the initials.
+            let initials = candidates[0].clone();
+            let suffixes = (2..=taken.len() + 2).map(|n| format!("{}{}", initials, n));
+            let key = candidates
+                .into_iter()
+                .chain(suffixes)
+                .find(|key| !taken.contains(key) && format.allows(key));
+            match key {
+                Some(key) => {
+                    taken.insert(key.clone());
+                    named.push((name.clone(), key));
+                }
+                None => combine(
+                    &mut error,
+                    Error::new(
+                        spans[name],
+                        format!(
+                            "no abbreviation of `{}` is allowed by format = \"{}\", pin it with #[compact(name = \"\")]",
+                            name,
+                            format.as_str()
+                        ),
+                    ),
+                ),
+            }
+        }
+        match error {
+            Some(error) => Err(error),
+            None => Ok(named),
+        }
+    }
+
+    /// Abbreviations of an identifier from the shortest: initials of its words, then more characters of the
+    /// last word, e.g. `ticket_type` gives `tt`, `tty`, `ttyp`, `ttype`
+    fn get_mnemonics(name: &str) -> Vec<String> {
+        // Words of snake_case and CamelCase identifiers.
+        let mut words: Vec<String> = Vec::new();
+        let mut prev_lower = false;
+        for c in name.chars() {
+            if c == '_' {
+                words.push(String::new());
+            } else {
+                if (c.is_uppercase() && prev_lower) || words.is_empty() {
+                    words.push(String::new());
+                }
+                if let Some(word) = words.last_mut() {
+                    word.push(c);
+                }
+            }
+            prev_lower = c.is_lowercase() || c.is_ascii_digit();
+        }
+        words.retain(|word| !word.is_empty());
+        let (last, init) = match words.split_last() {
+            Some(split) => split,
+            None => return vec![name.to_string()],
+        };
+        let initials: String = init.iter().filter_map(|word| word.chars().next()).collect();
+        (1..=last.chars().count())
+            .map(|len| format!("{}{}", initials, last.chars().take(len).collect::<String>()))
+            .collect()
+    }
+
+    /// Bijective base of the alphabet, `strategy_version = 2`: 'Z' is followed by 'aa'
+    fn get_bijective_name(mut value: usize, alphabet: &[char]) -> String {
+        let base = alphabet.len();
+        let mut name = "".to_string();
+
+        loop {
+            name.push(alphabet[value % base]);
+            value /= base;
+            if value == 0 {
+                break;
+            }
+            value -= 1;
+        }
+        name.chars().rev().collect()
+    }
+
+    /// Encode a hash as a name of exactly `len` characters of the alphabet
+    fn get_hash_name(mut hash: u64, len: usize, alphabet: &[char]) -> String {
+        let base = alphabet.len() as u64;
+        (0..len)
+            .map(|_| {
+                let c = alphabet[(hash % base) as usize];
+                hash /= base;
+                c
+            })
+            .collect()
     }
 
     /// Encode field names
-    /// Convert name vocabulary index to the base of ALPHABET
-    fn get_name(mut value: usize) -> String {
-        let base = ALPHABET.len();
+    /// Convert name vocabulary index to the base of the alphabet
+    ///
+    /// Frozen as `strategy_version = 1`: 'a' is a zero digit, so names 'aa'..'aZ' are never used.
+    fn get_name(mut value: usize, alphabet: &[char]) -> String {
+        let base = alphabet.len();
         let mut name = "".to_string();
 
         loop {
-            name.push(ALPHABET[(value % base) as usize]);
+            name.push(alphabet[value % base]);
             value /= base;
             if value == 0 {
                 break;
@@ -137,40 +1345,134 @@ impl NameMapper {
     }
 }
 
-impl Fold for NameMapper {
+/// Hash of a sorted table of identifiers and names
+fn fingerprint(table: &[(String, String)]) -> u64 {
+    let mut bytes = Vec::new();
+    for (name, key) in table {
+        bytes.extend_from_slice(format!("{}={}\n", name, key).as_bytes());
+    }
+    fnv1a(&bytes)
+}
+
+/// List identifiers whose names differ from a previous mapping, or the current names to diff without one
+fn describe_changes(table: &[(String, String)], previous: Option<&Mapping>) -> String {
+    let previous = match previous {
+        Some(previous) => previous,
+        None => {
+            let names: Vec<String> = table
+                .iter()
+                .map(|(name, key)| format!("{} = \"{}\"", name, key))
+                .collect();
+            return format!(
+                "no `previous` mapping to tell which names changed, names are now {}; \
+                 `export` them to diff against a mapping of the old names",
+                names.join(", ")
+            );
+        }
+    };
+    let mut changes = Vec::new();
+    for (name, key) in table {
+        // Previous mappings name fields of a variant qualified or like those of all variants.
+        let ident = name.rsplit('.').next().unwrap_or(name);
+        match previous.get(name).or_else(|| previous.get(ident)) {
+            Some(old) if old == key => {}
+            Some(old) => changes.push(format!("`{}` moved from \"{}\" to \"{}\"", name, old, key)),
+            None => changes.push(format!("`{}` added as \"{}\"", name, key)),
+        }
+    }
+    for (name, _) in &previous.entries {
+        if !table
+            .iter()
+            .any(|(n, _)| n == name || n.rsplit('.').next() == Some(name.as_str()))
+        {
+            changes.push(format!("`{}` removed", name));
+        }
+    }
+    if changes.is_empty() {
+        changes.push("no changes since the previous mapping".to_string());
+    }
+    changes.join(", ")
+}
+
+/// Identifier as serde names it, without `r#`
+fn unraw(name: &str) -> &str {
+    name.strip_prefix("r#").unwrap_or(name)
+}
+
+/// Report all errors at once
+fn combine(error: &mut Option<Error>, err: Error) {
+    match error {
+        Some(error) => error.combine(err),
+        None => *error = Some(err),
+    }
+}
+
+/// 64-bit FNV-1a, stable across platforms and compiler versions
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+impl NameMapper {
+    /// Add `#[serde(rename)]` with the short name of an identifier, and its aliases
+    fn rename(&self, attrs: &mut Vec<Attribute>, ident: &Ident, args: &FieldArgs) {
+        let name = args.ident(ident);
+        let rename = self.map.get(&name).expect("Failed to find mapping");
+        let renamed = SerdeNames::from_attrs(attrs).map_or(false, |serde| serde.renamed());
+        if !self.kept.contains(&name) && !renamed {
+            attrs.push(parse_quote!(#[serde(rename = #rename)]));
+        }
+        for alias in self.aliases.get(&name).into_iter().flatten() {
+            attrs.push(parse_quote!(#[serde(alias = #alias)]));
+        }
+    }
+}
+
+/// Insert map macros with the names of each pool
+struct Renamer {
+    pools: Vec<(Pool, NameMapper)>,
+    scope: Scope,
+    /// Variant of the fields being folded
+    variant: Option<String>,
+}
+
+impl Renamer {
+    fn mapper(&self, field: bool) -> &NameMapper {
+        let variant = if field { self.variant.as_deref() } else { None };
+        self.pools
+            .iter()
+            .find(|(pool, _)| pool.contains(field, variant))
+            .map(|(_, mapper)| mapper)
+            .expect("Failed to find pool")
+    }
+}
+
+impl Fold for Renamer {
     fn fold_field(&mut self, node: Field) -> Field {
         let mut node = node;
-        if let Some(ident) = &node.ident {
-            let rename = self
-                .map
-                .get(&ident.to_string())
-                .expect("Failed to find mapping");
-            if let Ok(mut attrs) =
-                Attribute::parse_outer.parse_str(&format!("#[serde(rename = \"{}\")]", rename))
-            {
-                if let Some(attr) = attrs.pop() {
-                    node.attrs.push(attr);
-                }
+        let args = FieldArgs::from_attrs(&node.attrs).unwrap_or_default();
+        node.attrs.retain(|attr| !args::is_compact(attr));
+        match node.ident.clone() {
+            Some(ident) if self.scope.fields => {
+                self.mapper(true).rename(&mut node.attrs, &ident, &args)
             }
-            fold::fold_field(self, node)
-        } else {
-            fold::fold_field(self, node)
+            _ => {}
         }
+        fold::fold_field(self, node)
     }
 
     fn fold_variant(&mut self, node: Variant) -> Variant {
         let mut node = node;
-        let rename = self
-            .map
-            .get(&node.ident.to_string())
-            .expect("Failed to find mapping");
-        if let Ok(mut attrs) =
-            Attribute::parse_outer.parse_str(&format!("#[serde(rename = \"{}\")]", rename))
-        {
-            if let Some(attr) = attrs.pop() {
-                node.attrs.push(attr);
-            }
+        let args = FieldArgs::from_attrs(&node.attrs).unwrap_or_default();
+        node.attrs.retain(|attr| !args::is_compact(attr));
+        if self.scope.variants {
+            let ident = node.ident.clone();
+            self.mapper(false).rename(&mut node.attrs, &ident, &args);
         }
-        fold::fold_variant(self, node)
+        self.variant = Some(node.ident.to_string());
+        let node = fold::fold_variant(self, node);
+        self.variant = None;
+        node
     }
 }
@@ -0,0 +1,232 @@
+//! Append-only lock file keeping short names stable across schema changes.
+//!
+//! ```text
+//! [Payment]
+//! @strategy = declaration
+//! currency = a
+//! id = b
+//! amount = c
+//! ```
+//!
+//! Types named in several pools have a section per pool, e.g. `[Event/fields]`.
+use crate::mapping::manifest_path;
+use proc_macro2::Span;
+use std::path::{Path, PathBuf};
+use std::{env, fs, io};
+use syn::{Error, LitStr};
+
+const HEADER: &str =
+    "# Generated by serde_compact. Entries are append-only, do not edit or remove them.\n";
+
+/// Set to refuse appending new names, e.g. in CI
+const LOCKED_ENV: &str = "SERDE_COMPACT_LOCKED";
+
+pub(crate) struct LockFile {
+    path: PathBuf,
+    span: Span,
+    sections: Vec<Section>,
+    dirty: bool,
+}
+
+struct Section {
+    name: String,
+    /// `@key = value` settings the names were assigned with
+    meta: Vec<(String, String)>,
+    entries: Vec<(String, String)>,
+}
+
+impl Section {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            meta: Vec::new(),
+            entries: Vec::new(),
+        }
+    }
+}
+
+impl LockFile {
+    /// Read the lock file, a missing file is treated as empty
+    pub fn open(path: &LitStr) -> syn::Result<Self> {
+        let mut lock = Self {
+            path: manifest_path(path)?,
+            span: path.span(),
+            sections: Vec::new(),
+            dirty: false,
+        };
+        match fs::read_to_string(&lock.path) {
+            Ok(text) => lock.sections = lock.parse(&text)?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => lock.dirty = true,
+            Err(err) => return Err(lock.error(format!("failed to read: {}", err))),
+        }
+        Ok(lock)
+    }
+
+    fn parse(&self, text: &str) -> syn::Result<Vec<Section>> {
+        let mut sections: Vec<Section> = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let at = |msg: String| self.error(format!("line {}: {}", number + 1, msg));
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                if sections.iter().any(|s| s.name == name) {
+                    return Err(at(format!("duplicate section `[{}]`", name)));
+                }
+                sections.push(Section::new(name));
+                continue;
+            }
+            let (ident, key) = match line.split_once('=') {
+                Some((ident, key)) => (ident.trim(), key.trim()),
+                None => return Err(at(format!("expected `ident = key`, found `{}`", line))),
+            };
+            let section = match sections.last_mut() {
+                Some(section) => section,
+                None => return Err(at(format!("`{}` is outside of a `[Type]` section", line))),
+            };
+            if ident.is_empty() || key.is_empty() {
+                return Err(at(format!("expected `ident = key`, found `{}`", line)));
+            }
+            if let Some(name) = ident.strip_prefix('@') {
+                section.meta.push((name.to_string(), key.to_string()));
+                continue;
+            }
+            if let Some((other, _)) = section.entries.iter().find(|(_, k)| k == key) {
+                return Err(at(format!(
+                    "`{}` is assigned to both `{}` and `{}`",
+                    key, other, ident
+                )));
+            }
+            if section.entries.iter().any(|(i, _)| i == ident) {
+                return Err(at(format!("`{}` is listed twice", ident)));
+            }
+            section.entries.push((ident.to_string(), key.to_string()));
+        }
+        Ok(sections)
+    }
+
+    /// Locked `(ident, key)` pairs of a type
+    pub fn entries(&self, section: &str) -> &[(String, String)] {
+        self.sections
+            .iter()
+            .find(|s| s.name == section)
+            .map(|s| s.entries.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// `@key` setting of a type
+    pub fn meta(&self, section: &str, key: &str) -> Option<&str> {
+        self.sections
+            .iter()
+            .find(|s| s.name == section)
+            .and_then(|s| s.meta.iter().find(|(k, _)| k == key))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Record a setting of a type, it can't change once names are locked.
+    /// Sections without a setting are locked with its default.
+    pub fn settle(
+        &mut self,
+        section: &str,
+        key: &str,
+        value: &str,
+        default: &str,
+    ) -> syn::Result<()> {
+        let locked = match self.meta(section, key) {
+            Some(locked) => Some(locked),
+            None if !self.entries(section).is_empty() => Some(default),
+            None => None,
+        };
+        match locked {
+            Some(locked) if locked != value => {
+                Err(self.error(format!("`{}` is locked with {} = {}", section, key, locked)))
+            }
+            None if value != default => {
+                self.set_meta(section, key, value);
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn set_meta(&mut self, section: &str, key: &str, value: &str) {
+        let index = self.section_index(section);
+        let meta = &mut self.sections[index].meta;
+        match meta.iter_mut().find(|(k, _)| k == key) {
+            Some(entry) => entry.1 = value.to_string(),
+            None => meta.push((key.to_string(), value.to_string())),
+        }
+        self.dirty = true;
+    }
+
+    fn section_index(&mut self, section: &str) -> usize {
+        match self.sections.iter().position(|s| s.name == section) {
+            Some(index) => index,
+            None => {
+                self.sections.push(Section::new(section));
+                self.sections.len() - 1
+            }
+        }
+    }
+
+    /// Append newly assigned names of a type
+    pub fn append(&mut self, section: &str, entries: Vec<(String, String)>) -> syn::Result<()> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+        if env::var_os(LOCKED_ENV).map_or(false, |v| !v.is_empty() && v != "0") {
+            let idents: Vec<String> = entries.iter().map(|(i, _)| format!("`{}`", i)).collect();
+            return Err(self.error(format!(
+                "{} of `{}` missing and {} is set",
+                idents.join(", "),
+                section,
+                LOCKED_ENV
+            )));
+        }
+        let index = self.section_index(section);
+        self.sections[index].entries.extend(entries);
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Write changes, if any. Expansions by rust-analyzer see unsaved, half-typed identifiers and write nothing.
+    pub fn save(&self) -> syn::Result<()> {
+        if !self.dirty || in_rust_analyzer() {
+            return Ok(());
+        }
+        let mut text = HEADER.to_string();
+        for section in &self.sections {
+            text.push_str(&format!("\n[{}]\n", section.name));
+            for (key, value) in &section.meta {
+                text.push_str(&format!("@{} = {}\n", key, value));
+            }
+            for (ident, key) in &section.entries {
+                text.push_str(&format!("{} = {}\n", ident, key));
+            }
+        }
+        // Write a temporary file and rename it to never leave a truncated lock behind.
+        let tmp = self.path.with_extension("lock.tmp");
+        let dir = self.path.parent().unwrap_or_else(|| Path::new("."));
+        fs::create_dir_all(dir)
+            .and_then(|_| fs::write(&tmp, text))
+            .and_then(|_| fs::rename(&tmp, &self.path))
+            .map_err(|err| self.error(format!("failed to write: {}", err)))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn error(&self, msg: String) -> Error {
+        Error::new(self.span, format!("{}: {}", self.path.display(), msg))
+    }
+}
+
+/// Whether the macro runs in rust-analyzer's proc-macro server rather than in rustc
+fn in_rust_analyzer() -> bool {
+    env::current_exe()
+        .ok()
+        .and_then(|exe| Some(exe.file_name()?.to_str()?.contains("rust-analyzer")))
+        .unwrap_or(false)
+}
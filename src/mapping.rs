@@ -0,0 +1,80 @@
+//! Mapping files, JSON objects of identifiers and their names:
+//!
+//! ```json
+//! {"event_id": "a", "user_id": "b"}
+//! ```
+use proc_macro2::Span;
+use std::path::PathBuf;
+use std::{env, fs};
+use syn::{Error, LitStr};
+
+/// Identifiers and names of a type
+#[derive(Clone)]
+pub(crate) struct Mapping {
+    /// Span of the argument the mapping comes from
+    pub span: Span,
+    pub entries: Vec<(String, String)>,
+    /// File the mapping was read from
+    pub path: Option<PathBuf>,
+}
+
+impl Mapping {
+    pub fn read(path: &LitStr) -> syn::Result<Self> {
+        let full_path = manifest_path(path)?;
+        let error =
+            |msg: String| Error::new(path.span(), format!("{}: {}", full_path.display(), msg));
+        let text = fs::read_to_string(&full_path).map_err(|err| error(err.to_string()))?;
+        let value: serde_json::Value =
+            serde_json::from_str(&text).map_err(|err| error(err.to_string()))?;
+        let object = value
+            .as_object()
+            .ok_or_else(|| error("expected an object of identifiers and names".to_string()))?;
+        let mut entries = Vec::new();
+        for (ident, key) in object {
+            match key.as_str() {
+                Some(key) => entries.push((ident.clone(), key.to_string())),
+                None => return Err(error(format!("name of `{}` is not a string", ident))),
+            }
+        }
+        Ok(Self {
+            span: path.span(),
+            entries,
+            path: Some(full_path),
+        })
+    }
+
+    /// Write identifiers and names, unless the file is up to date
+    pub fn write(path: &LitStr, entries: &[(String, String)]) -> syn::Result<()> {
+        let full_path = manifest_path(path)?;
+        let error =
+            |msg: String| Error::new(path.span(), format!("{}: {}", full_path.display(), msg));
+        let object: serde_json::Map<String, serde_json::Value> = entries
+            .iter()
+            .map(|(ident, key)| (ident.clone(), serde_json::Value::String(key.clone())))
+            .collect();
+        let mut text =
+            serde_json::to_string_pretty(&object).map_err(|err| error(err.to_string()))?;
+        text.push('\n');
+        if fs::read_to_string(&full_path).map_or(false, |old| old == text) {
+            return Ok(());
+        }
+        let tmp = PathBuf::from(format!("{}.tmp", full_path.display()));
+        fs::write(&tmp, text)
+            .and_then(|_| fs::rename(&tmp, &full_path))
+            .map_err(|err| error(format!("failed to write: {}", err)))
+    }
+
+    pub fn get(&self, ident: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(i, _)| i == ident)
+            .map(|(_, key)| key.as_str())
+    }
+}
+
+/// Resolve a path relative to `CARGO_MANIFEST_DIR`
+pub(crate) fn manifest_path(path: &LitStr) -> syn::Result<PathBuf> {
+    let dir = env::var("CARGO_MANIFEST_DIR")
+        .map_err(|_| Error::new(path.span(), "CARGO_MANIFEST_DIR is not set"))?;
+    Ok(PathBuf::from(dir).join(path.value()))
+}
@@ -0,0 +1,55 @@
+//! Sample payloads, one verbose JSON value per line:
+//!
+//! ```json
+//! {"ReservationConfirmation": {"event_id": 1, "user_id": 2}}
+//! {"Cancellation": {"event_id": 1}}
+//! ```
+use crate::mapping::manifest_path;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use syn::{Error, LitStr};
+
+/// Occurrences of identifiers in sample payloads
+pub(crate) struct Profile {
+    pub counts: HashMap<String, u64>,
+    pub path: PathBuf,
+}
+
+impl Profile {
+    /// Count object keys and strings, which are unit variants
+    pub fn read(path: &LitStr) -> syn::Result<Self> {
+        let full_path = manifest_path(path)?;
+        let error =
+            |msg: String| Error::new(path.span(), format!("{}: {}", full_path.display(), msg));
+        let text = fs::read_to_string(&full_path).map_err(|err| error(err.to_string()))?;
+        let mut counts = HashMap::new();
+        for (number, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let value: Value = serde_json::from_str(line)
+                .map_err(|err| error(format!("line {}: {}", number + 1, err)))?;
+            count(&value, &mut counts);
+        }
+        Ok(Self {
+            counts,
+            path: full_path,
+        })
+    }
+}
+
+fn count(value: &Value, counts: &mut HashMap<String, u64>) {
+    match value {
+        Value::Object(object) => {
+            for (key, value) in object {
+                *counts.entry(key.clone()).or_default() += 1;
+                count(value, counts);
+            }
+        }
+        Value::Array(values) => values.iter().for_each(|value| count(value, counts)),
+        Value::String(string) => *counts.entry(string.clone()).or_default() += 1,
+        _ => {}
+    }
+}
@@ -11,6 +11,22 @@ mod tests {
         };
     }
 
+    /// Struct with more fields than single-character names
+    macro_rules! wide {
+        ($name:ident, $($args:tt)*) => {
+            #[compact($($args)*)]
+            #[derive(Serialize, Default)]
+            struct $name {
+                f00: u8, f01: u8, f02: u8, f03: u8, f04: u8, f05: u8, f06: u8, f07: u8, f08: u8,
+                f09: u8, f10: u8, f11: u8, f12: u8, f13: u8, f14: u8, f15: u8, f16: u8, f17: u8,
+                f18: u8, f19: u8, f20: u8, f21: u8, f22: u8, f23: u8, f24: u8, f25: u8, f26: u8,
+                f27: u8, f28: u8, f29: u8, f30: u8, f31: u8, f32: u8, f33: u8, f34: u8, f35: u8,
+                f36: u8, f37: u8, f38: u8, f39: u8, f40: u8, f41: u8, f42: u8, f43: u8, f44: u8,
+                f45: u8, f46: u8, f47: u8, f48: u8, f49: u8, f50: u8, f51: u8, f52: u8, f53: u8
+            }
+        };
+    }
+
     #[test]
     fn test() {
         #[derive(Serialize, Deserialize, PartialEq, Debug)]
@@ -135,5 +151,1158 @@ mod tests {
         };
         let ser = serde_json::to_string(&s).unwrap();
         assert_eq!(ser, r#"{"b":{"c":1,"e":1,"d":0}}"#);
+
+        // Raw identifiers are sorted with `r#`, so `r#type` comes before `rate`.
+        #[compact]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct Rate {
+            rate: i32,
+            r#type: i32,
+        }
+
+        let r = Rate { rate: 1, r#type: 2 };
+        let ser = serde_json::to_string(&r).unwrap();
+        assert_eq!(ser, r#"{"b":1,"a":2}"#);
+        test_serde!(Rate, r);
+    }
+
+    #[test]
+    fn lock() {
+        // `currency` and `id` are locked in tests/compact.lock, `amount` and `memo` were added later.
+        #[compact(lock = "tests/compact.lock")]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct Payment {
+            amount: i64,
+            currency: String,
+            id: u32,
+            memo: String,
+        }
+
+        let p = Payment {
+            amount: 1,
+            currency: "EUR".to_string(),
+            id: 2,
+            memo: "".to_string(),
+        };
+        let ser = serde_json::to_string(&p).unwrap();
+        assert_eq!(ser, r#"{"c":1,"a":"EUR","b":2,"d":""}"#);
+        test_serde!(Payment, p);
+
+        // Written by the first build: `Old` locks `currency` and `id`, `New` in the same section appends
+        // `amount` and `memo`.
+        #[compact(lock = "target/tests/append.lock", lock_section = "billing::Payment")]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct Old {
+            currency: String,
+            id: u32,
+        }
+
+        #[compact(lock = "target/tests/append.lock", lock_section = "billing::Payment")]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct New {
+            amount: i64,
+            currency: String,
+            id: u32,
+            memo: String,
+        }
+
+        let old = Old {
+            currency: "EUR".to_string(),
+            id: 2,
+        };
+        assert_eq!(serde_json::to_string(&old).unwrap(), r#"{"a":"EUR","b":2}"#);
+        let new = New {
+            amount: 1,
+            currency: "EUR".to_string(),
+            id: 2,
+            memo: "".to_string(),
+        };
+        assert_eq!(serde_json::to_string(&new).unwrap(), ser);
+        let lock = std::fs::read_to_string(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/target/tests/append.lock"
+        ))
+        .unwrap();
+        assert!(lock.contains("[billing::Payment]\ncurrency = a\nid = b\namount = c\nmemo = d\n"));
+    }
+
+    #[test]
+    fn pinned_names() {
+        #[compact]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        enum Event {
+            #[compact(name = "a")]
+            Tick { seq: u64 },
+            Quote {
+                #[compact(name = "p")]
+                price: f64,
+                seq: u64,
+            },
+        }
+
+        let e = Event::Quote { price: 1.5, seq: 7 };
+        let ser = serde_json::to_string(&e).unwrap();
+        assert_eq!(ser, r#"{"b":{"p":1.5,"c":7}}"#);
+        test_serde!(Event, e);
+        test_serde!(Event, Event::Tick { seq: 1 });
+
+        // Names needing escapes are kept as written.
+        #[compact(previous(user_id = "u\\"))]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct Quoted {
+            #[compact(name = "a\"b")]
+            event_id: i32,
+            user_id: i32,
+        }
+
+        let q = Quoted {
+            event_id: 1,
+            user_id: 2,
+        };
+        let ser = serde_json::to_string(&q).unwrap();
+        assert_eq!(ser, r#"{"a\"b":1,"a":2}"#);
+        test_serde!(Quoted, q);
+        let de: Quoted = serde_json::from_str(r#"{"a\"b":1,"u\\":2}"#).unwrap();
+        assert_eq!(de, q);
+    }
+
+    #[test]
+    fn declaration_strategy() {
+        #[compact(strategy = "declaration")]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        enum Command {
+            Start {
+                id: u32,
+                name: String,
+                #[compact(since = 1)]
+                reason: String,
+            },
+            Stop {
+                id: u32,
+            },
+            #[compact(since = 1)]
+            Pause {
+                id: u32,
+                #[compact(since = 1)]
+                until: u64,
+            },
+        }
+
+        assert_eq!(Command::COMPACT_STRATEGY, "declaration");
+        let c = Command::Start {
+            id: 1,
+            name: "".to_string(),
+            reason: "".to_string(),
+        };
+        let ser = serde_json::to_string(&c).unwrap();
+        assert_eq!(ser, r#"{"a":{"b":1,"c":"","e":""}}"#);
+        test_serde!(Command, c);
+        let c = Command::Pause { id: 1, until: 2 };
+        let ser = serde_json::to_string(&c).unwrap();
+        assert_eq!(ser, r#"{"f":{"b":1,"g":2}}"#);
+        test_serde!(Command, c);
+
+        #[compact(strategy = "declaration")]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        enum V1 {
+            A { x: i32 },
+            B { y: i32 },
+        }
+
+        // Appended to `A`, `z` would come before `B` and `y` without `since`.
+        #[compact(strategy = "declaration")]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        enum Unmarked {
+            A { x: i32, z: i32 },
+            B { y: i32 },
+        }
+
+        #[compact(strategy = "declaration")]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        enum V2 {
+            A {
+                x: i32,
+                #[compact(since = 1)]
+                z: i32,
+            },
+            B {
+                y: i32,
+            },
+        }
+
+        let v1 = serde_json::to_string(&V1::B { y: 1 }).unwrap();
+        assert_eq!(v1, r#"{"c":{"d":1}}"#);
+        let unmarked = serde_json::to_string(&Unmarked::B { y: 1 }).unwrap();
+        assert_eq!(unmarked, r#"{"d":{"e":1}}"#);
+        assert_eq!(serde_json::to_string(&V2::B { y: 1 }).unwrap(), v1);
+        let a = V2::A { x: 1, z: 2 };
+        assert_eq!(serde_json::to_string(&a).unwrap(), r#"{"a":{"b":1,"e":2}}"#);
+        test_serde!(V2, a);
+    }
+
+    #[test]
+    fn hash_strategy() {
+        #[compact(strategy = "hash")]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct EventV1 {
+            event_id: i32,
+            user_id: i32,
+        }
+
+        #[compact(strategy = "hash")]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct EventV2 {
+            amount: i32,
+            event_id: i32,
+            ticket_type: i32,
+            user_id: i32,
+        }
+
+        // Names don't depend on other fields unless they collide.
+        let v1 = serde_json::to_value(EventV1 {
+            event_id: 1,
+            user_id: 2,
+        })
+        .unwrap();
+        let v2 = serde_json::to_value(EventV2 {
+            amount: 0,
+            event_id: 1,
+            ticket_type: 0,
+            user_id: 2,
+        })
+        .unwrap();
+        for (key, value) in v1.as_object().unwrap() {
+            assert_eq!(key.len(), 1);
+            assert_eq!(&v2[key], value);
+        }
+        test_serde!(
+            EventV2,
+            EventV2 {
+                amount: 3,
+                event_id: 1,
+                ticket_type: 4,
+                user_id: 2,
+            }
+        );
+
+        // `field101` collides with `event_id` at one character, both grow to two.
+        #[compact(strategy = "hash")]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct Single {
+            event_id: i32,
+        }
+
+        #[compact(strategy = "hash")]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct Colliding {
+            event_id: i32,
+            field101: i32,
+        }
+
+        let ser = serde_json::to_string(&Single { event_id: 1 }).unwrap();
+        assert_eq!(ser, r#"{"P":1}"#);
+        let c = Colliding {
+            event_id: 1,
+            field101: 2,
+        };
+        let ser = serde_json::to_string(&c).unwrap();
+        assert_eq!(ser, r#"{"PL":1,"PN":2}"#);
+    }
+
+    #[test]
+    fn reserved_names() {
+        // `bravo` and `charlie` were removed.
+        #[compact(reserved = ["b"], reserved_idents = ["charlie"])]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct Removed {
+            alpha: i32,
+            delta: i32,
+            echo: i32,
+        }
+
+        let r = Removed {
+            alpha: 1,
+            delta: 2,
+            echo: 3,
+        };
+        let ser = serde_json::to_string(&r).unwrap();
+        assert_eq!(ser, r#"{"a":1,"d":2,"e":3}"#);
+        test_serde!(Removed, r);
+
+        #[compact(pools = "split")]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        enum Before {
+            Alpha { x: i32, y: i32 },
+            Beta,
+        }
+
+        // `Beta` was removed, fields keep their names.
+        #[compact(pools = "split", reserved_idents = ["variants:Beta"])]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        enum After {
+            Alpha { x: i32, y: i32 },
+        }
+
+        let before = serde_json::to_string(&Before::Alpha { x: 1, y: 2 }).unwrap();
+        assert_eq!(before, r#"{"a":{"a":1,"b":2}}"#);
+        let after = serde_json::to_string(&After::Alpha { x: 1, y: 2 }).unwrap();
+        assert_eq!(after, before);
+        let de: After = serde_json::from_str(&before).unwrap();
+        assert_eq!(de, After::Alpha { x: 1, y: 2 });
+
+        #[compact(pools = "per-variant", reserved_idents = ["Alpha.z"])]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        enum Fields {
+            Alpha { x: i32, y: i32 },
+            Gamma { z: i32 },
+        }
+
+        let f = Fields::Alpha { x: 1, y: 2 };
+        assert_eq!(serde_json::to_string(&f).unwrap(), r#"{"a":{"a":1,"b":2}}"#);
+        let g = Fields::Gamma { z: 3 };
+        assert_eq!(serde_json::to_string(&g).unwrap(), r#"{"b":{"a":3}}"#);
+        test_serde!(Fields, f);
+    }
+
+    #[test]
+    fn renamed_identifiers() {
+        #[compact]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct Before {
+            event_id: i32,
+            user_id: i32,
+        }
+
+        #[compact]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct After {
+            #[compact(was = "user_id")]
+            account_id: i32,
+            event_id: i32,
+        }
+
+        let ser = serde_json::to_string(&Before {
+            event_id: 1,
+            user_id: 2,
+        })
+        .unwrap();
+        assert_eq!(ser, r#"{"a":1,"b":2}"#);
+        let de: After = serde_json::from_str(&ser).unwrap();
+        assert_eq!(
+            de,
+            After {
+                account_id: 2,
+                event_id: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn accept_verbose() {
+        #[compact(accept_verbose)]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        enum Query {
+            Confirm {
+                event_id: i32,
+                #[compact(was = "user_id")]
+                account_id: i32,
+            },
+        }
+
+        let q = Query::Confirm {
+            event_id: 1,
+            account_id: 2,
+        };
+        assert_eq!(serde_json::to_string(&q).unwrap(), r#"{"a":{"b":1,"c":2}}"#);
+        for verbose in [
+            r#"{"Confirm":{"event_id":1,"account_id":2}}"#,
+            r#"{"Confirm":{"event_id":1,"user_id":2}}"#,
+            r#"{"a":{"event_id":1,"c":2}}"#,
+        ] {
+            let de: Query = serde_json::from_str(verbose).unwrap();
+            assert_eq!(de, q);
+        }
+        test_serde!(Query, q);
+
+        // No short name is the original name of another identifier.
+        #[compact(accept_verbose, keep = ["kind"])]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct Event {
+            d: i32,
+            alpha: i32,
+            beta: i32,
+            gamma: i32,
+            r#type: i32,
+            r#kind: i32,
+        }
+
+        let e = Event {
+            d: 1,
+            alpha: 2,
+            beta: 3,
+            gamma: 4,
+            r#type: 5,
+            r#kind: 6,
+        };
+        let ser = serde_json::to_string(&e).unwrap();
+        assert_eq!(ser, r#"{"c":1,"a":2,"b":3,"e":4,"f":5,"kind":6}"#);
+        test_serde!(Event, e);
+        let verbose = r#"{"d":1,"alpha":2,"beta":3,"gamma":4,"type":5,"kind":6}"#;
+        let de: Event = serde_json::from_str(verbose).unwrap();
+        assert_eq!(de, e);
+    }
+
+    #[test]
+    fn previous_mappings() {
+        #[compact(previous = "tests/mappings/v1.json", previous(event_id = "ev"))]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct Event {
+            event_id: i32,
+            user_id: i32,
+        }
+
+        let e = Event {
+            event_id: 1,
+            user_id: 2,
+        };
+        assert_eq!(serde_json::to_string(&e).unwrap(), r#"{"a":1,"b":2}"#);
+        for old in [r#"{"e":1,"u":2}"#, r#"{"ev":1,"u":2}"#] {
+            let de: Event = serde_json::from_str(old).unwrap();
+            assert_eq!(de, e);
+        }
+    }
+
+    #[test]
+    fn fingerprint() {
+        #[compact(fingerprint = 0xbcb44aff799e2def)]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct Event {
+            event_id: i32,
+            user_id: i32,
+        }
+
+        #[compact]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct Reordered {
+            user_id: i32,
+            event_id: i32,
+        }
+
+        assert_eq!(Event::COMPACT_FINGERPRINT, 0xbcb44aff799e2def);
+        assert_eq!(Reordered::COMPACT_FINGERPRINT, Event::COMPACT_FINGERPRINT);
+    }
+
+    #[test]
+    fn strategy_version() {
+        wide!(V1, strategy_version = 1);
+        wide!(V2, strategy_version = 2);
+
+        let v1 = serde_json::to_value(V1::default()).unwrap();
+        assert!(v1.get("ba").is_some() && v1.get("bb").is_some());
+        let v2 = serde_json::to_value(V2::default()).unwrap();
+        assert!(v2.get("aa").is_some() && v2.get("ab").is_some());
+    }
+
+    #[test]
+    fn kept_names() {
+        #[compact(keep = ["exp", "iat", "sub"])]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct Claims {
+            sub: String,
+            exp: u64,
+            iat: u64,
+            roles: Vec<String>,
+            #[compact(keep)]
+            a: bool,
+            user_name: String,
+        }
+
+        let c = Claims {
+            sub: "1".to_string(),
+            exp: 2,
+            iat: 3,
+            roles: Vec::new(),
+            a: true,
+            user_name: "".to_string(),
+        };
+        let ser = serde_json::to_string(&c).unwrap();
+        assert_eq!(ser, r#"{"sub":"1","exp":2,"iat":3,"b":[],"a":true,"c":""}"#);
+        test_serde!(Claims, c);
+    }
+
+    #[test]
+    fn short_identifiers() {
+        #[compact(min_len = 3)]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct Point {
+            id: u32,
+            x: f64,
+            amount: i64,
+            currency: String,
+        }
+
+        let p = Point {
+            id: 1,
+            x: 0.5,
+            amount: 2,
+            currency: "".to_string(),
+        };
+        let ser = serde_json::to_string(&p).unwrap();
+        assert_eq!(ser, r#"{"id":1,"x":0.5,"a":2,"b":""}"#);
+        test_serde!(Point, p);
+
+        #[compact(shorter_only)]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct Shorter {
+            b: i32,
+            id: i32,
+            name: String,
+        }
+
+        let s = Shorter {
+            b: 1,
+            id: 2,
+            name: "".to_string(),
+        };
+        let ser = serde_json::to_string(&s).unwrap();
+        assert_eq!(ser, r#"{"b":1,"a":2,"c":""}"#);
+        test_serde!(Shorter, s);
+    }
+
+    #[test]
+    fn scope() {
+        #[compact(fields)]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        enum Event {
+            Login { user_id: i32 },
+            Logout { user_id: i32, reason: String },
+        }
+
+        let e = Event::Logout {
+            user_id: 1,
+            reason: "idle".to_string(),
+        };
+        let ser = serde_json::to_string(&e).unwrap();
+        assert_eq!(ser, r#"{"Logout":{"b":1,"a":"idle"}}"#);
+        test_serde!(Event, e);
+
+        #[compact(variants)]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        enum Tag {
+            Login { user_id: i32 },
+            Logout { user_id: i32 },
+        }
+
+        let t = Tag::Logout { user_id: 1 };
+        let ser = serde_json::to_string(&t).unwrap();
+        assert_eq!(ser, r#"{"b":{"user_id":1}}"#);
+        test_serde!(Tag, t);
+
+        #[compact(variants = false)]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        enum Fields {
+            Login { user_id: i32 },
+            Logout { user_id: i32, reason: String },
+        }
+
+        let f = Fields::Login { user_id: 1 };
+        let ser = serde_json::to_string(&f).unwrap();
+        assert_eq!(ser, r#"{"Login":{"b":1}}"#);
+        test_serde!(Fields, f);
+    }
+
+    #[test]
+    fn split_pools() {
+        #[compact(pools = "split")]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        enum Event {
+            Login { user_id: i32 },
+            Logout { user_id: i32, reason: String },
+        }
+
+        let e = Event::Logout {
+            user_id: 1,
+            reason: "idle".to_string(),
+        };
+        let ser = serde_json::to_string(&e).unwrap();
+        assert_eq!(ser, r#"{"b":{"b":1,"a":"idle"}}"#);
+        test_serde!(Event, e);
+    }
+
+    #[test]
+    fn per_variant_pools() {
+        #[compact(pools = "per-variant")]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        enum Message {
+            Request { id: u32, method: String },
+            Response { result: u32 },
+        }
+
+        let req = Message::Request {
+            id: 1,
+            method: "get".to_string(),
+        };
+        let ser = serde_json::to_string(&req).unwrap();
+        assert_eq!(ser, r#"{"a":{"a":1,"b":"get"}}"#);
+        test_serde!(Message, req);
+
+        let res = Message::Response { result: 2 };
+        let ser = serde_json::to_string(&res).unwrap();
+        assert_eq!(ser, r#"{"b":{"a":2}}"#);
+        test_serde!(Message, res);
+
+        // Exported per-variant tables qualify fields with their variant.
+        #[compact(pools = "per-variant", previous = "tests/mappings/per_variant.json")]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        enum Migrated {
+            Request { id: u32, method: String },
+            Response { result: u32 },
+        }
+
+        let de: Migrated = serde_json::from_str(r#"{"q":{"i":1,"m":"get"}}"#).unwrap();
+        assert_eq!(
+            de,
+            Migrated::Request {
+                id: 1,
+                method: "get".to_string(),
+            }
+        );
+        let de: Migrated = serde_json::from_str(r#"{"s":{"r":2}}"#).unwrap();
+        assert_eq!(de, Migrated::Response { result: 2 });
+    }
+
+    #[test]
+    fn priority() {
+        #[compact]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct Event {
+            debug_info: String,
+            #[compact(priority = 1)]
+            user_id: i32,
+            #[compact(hot)]
+            timestamp: u64,
+        }
+
+        let e = Event {
+            debug_info: "".to_string(),
+            user_id: 1,
+            timestamp: 2,
+        };
+        let ser = serde_json::to_string(&e).unwrap();
+        assert_eq!(ser, r#"{"c":"","b":1,"a":2}"#);
+        test_serde!(Event, e);
+    }
+
+    #[test]
+    fn profile() {
+        #[compact(
+            profile = "tests/samples/events.ndjson",
+            export = "tests/mappings/profiled.json"
+        )]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct Event {
+            event_id: i32,
+            user_id: i32,
+            amount: i64,
+        }
+
+        let e = Event {
+            event_id: 1,
+            user_id: 2,
+            amount: 3,
+        };
+        let ser = serde_json::to_string(&e).unwrap();
+        assert_eq!(ser, r#"{"c":1,"b":2,"a":3}"#);
+        test_serde!(Event, e);
+
+        // Samples are counted by the names serde gives identifiers.
+        #[compact(profile = "tests/samples/camel.ndjson", accept_verbose)]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct Renamed {
+            amount: i64,
+            event_id: i32,
+            user_id: i32,
+        }
+
+        let r = Renamed {
+            amount: 1,
+            event_id: 2,
+            user_id: 3,
+        };
+        let ser = serde_json::to_string(&r).unwrap();
+        assert_eq!(ser, r#"{"c":1,"b":2,"a":3}"#);
+    }
+
+    #[test]
+    fn mnemonic_strategy() {
+        #[compact(strategy = "mnemonic")]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct Ticket {
+            event_id: i32,
+            user_id: i32,
+            ticket_type: i32,
+            ticket_time: u64,
+            amount: i64,
+        }
+
+        let t = Ticket {
+            event_id: 1,
+            user_id: 2,
+            ticket_type: 3,
+            ticket_time: 4,
+            amount: 5,
+        };
+        let ser = serde_json::to_string(&t).unwrap();
+        assert_eq!(ser, r#"{"ei":1,"ui":2,"tty":3,"tt":4,"a":5}"#);
+        test_serde!(Ticket, t);
+
+        #[compact(strategy = "mnemonic")]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        enum Query {
+            ReservationConfirmation { event_id: i32 },
+        }
+
+        let q = Query::ReservationConfirmation { event_id: 1 };
+        let ser = serde_json::to_string(&q).unwrap();
+        assert_eq!(ser, r#"{"RC":{"ei":1}}"#);
+        test_serde!(Query, q);
+    }
+
+    #[test]
+    fn alphabets() {
+        wide!(Wide, alphabet = "alnum");
+
+        let wide = serde_json::to_value(Wide::default()).unwrap();
+        assert!(wide.get("0").is_some() && wide.get("1").is_some());
+
+        #[compact(alphabet = "custom:xyz")]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct Point {
+            a: i32,
+            b: i32,
+            c: i32,
+            d: i32,
+        }
+
+        let p = Point {
+            a: 1,
+            b: 2,
+            c: 3,
+            d: 4,
+        };
+        let ser = serde_json::to_string(&p).unwrap();
+        assert_eq!(ser, r#"{"x":1,"y":2,"z":3,"yx":4}"#);
+        test_serde!(Point, p);
+    }
+
+    #[test]
+    fn formats() {
+        wide!(Yaml, format = "yaml");
+
+        let yaml = serde_json::to_value(Yaml::default()).unwrap();
+        assert!(yaml.get("n").is_none() && yaml.get("y").is_none());
+        assert!(yaml.get("z").is_some() && yaml.get("B").is_some());
+
+        #[compact(format = "env", strategy = "mnemonic")]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct Config {
+            database_url: String,
+            #[compact(name = "p")]
+            port: u16,
+        }
+
+        let c = Config {
+            database_url: "".to_string(),
+            port: 80,
+        };
+        let ser = serde_json::to_string(&c).unwrap();
+        assert_eq!(ser, r#"{"du":"","p":80}"#);
+        test_serde!(Config, c);
+    }
+
+    #[test]
+    fn numeric_keys() {
+        #[compact(keys = "numeric", strategy = "declaration")]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        enum Event {
+            Login { user_id: i32 },
+            Logout { user_id: i32, reason: String },
+        }
+
+        let e = Event::Logout {
+            user_id: 1,
+            reason: "idle".to_string(),
+        };
+        let ser = serde_json::to_string(&e).unwrap();
+        assert_eq!(ser, r#"{"2":{"1":1,"3":"idle"}}"#);
+        test_serde!(Event, e);
+
+        wide!(Wide, keys = "numeric", strategy_version = 2);
+
+        let wide = serde_json::to_value(Wide::default()).unwrap();
+        assert_eq!(wide.get("10"), Some(&serde_json::json!(0)));
+    }
+
+    /// Self-describing format keeping integer map keys apart from strings, like CBOR and MessagePack
+    mod binary {
+        use serde::de::value::Error;
+        use serde::de::{self, DeserializeSeed, MapAccess, Visitor};
+        use serde::forward_to_deserialize_any;
+        use serde::ser::{self, Impossible, Serialize, SerializeMap};
+
+        #[derive(PartialEq, Debug)]
+        pub enum Value {
+            Int(i64),
+            Str(String),
+            Null,
+            Map(Vec<(Value, Value)>),
+        }
+
+        pub fn to_value<T: Serialize>(value: &T) -> Value {
+            value.serialize(Serializer).unwrap()
+        }
+
+        pub fn from_value<'de, T: de::Deserialize<'de>>(value: Value) -> Result<T, Error> {
+            T::deserialize(value)
+        }
+
+        struct Serializer;
+
+        macro_rules! unsupported {
+            ($($method:ident($($arg:ty),*) -> $ok:ty;)*) => {
+                $(fn $method(self, $(_: $arg),*) -> Result<$ok, Error> {
+                    Err(ser::Error::custom(stringify!($method)))
+                })*
+            };
+        }
+
+        impl ser::Serializer for Serializer {
+            type Ok = Value;
+            type Error = Error;
+            type SerializeSeq = Impossible<Value, Error>;
+            type SerializeTuple = Impossible<Value, Error>;
+            type SerializeTupleStruct = Impossible<Value, Error>;
+            type SerializeTupleVariant = Impossible<Value, Error>;
+            type SerializeMap = Map;
+            type SerializeStruct = Impossible<Value, Error>;
+            type SerializeStructVariant = Impossible<Value, Error>;
+
+            fn serialize_i8(self, v: i8) -> Result<Value, Error> {
+                Ok(Value::Int(v.into()))
+            }
+            fn serialize_i16(self, v: i16) -> Result<Value, Error> {
+                Ok(Value::Int(v.into()))
+            }
+            fn serialize_i32(self, v: i32) -> Result<Value, Error> {
+                Ok(Value::Int(v.into()))
+            }
+            fn serialize_i64(self, v: i64) -> Result<Value, Error> {
+                Ok(Value::Int(v))
+            }
+            fn serialize_u8(self, v: u8) -> Result<Value, Error> {
+                Ok(Value::Int(v.into()))
+            }
+            fn serialize_u16(self, v: u16) -> Result<Value, Error> {
+                Ok(Value::Int(v.into()))
+            }
+            fn serialize_u32(self, v: u32) -> Result<Value, Error> {
+                Ok(Value::Int(v.into()))
+            }
+            fn serialize_u64(self, v: u64) -> Result<Value, Error> {
+                i64::try_from(v).map(Value::Int).map_err(ser::Error::custom)
+            }
+            fn serialize_str(self, v: &str) -> Result<Value, Error> {
+                Ok(Value::Str(v.to_string()))
+            }
+            fn serialize_none(self) -> Result<Value, Error> {
+                Ok(Value::Null)
+            }
+            fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<Value, Error> {
+                value.serialize(self)
+            }
+            fn serialize_unit(self) -> Result<Value, Error> {
+                Ok(Value::Null)
+            }
+            fn serialize_newtype_struct<T: ?Sized + Serialize>(
+                self,
+                _: &'static str,
+                value: &T,
+            ) -> Result<Value, Error> {
+                value.serialize(self)
+            }
+            fn serialize_newtype_variant<T: ?Sized + Serialize>(
+                self,
+                _: &'static str,
+                _: u32,
+                _: &'static str,
+                _: &T,
+            ) -> Result<Value, Error> {
+                Err(ser::Error::custom("serialize_newtype_variant"))
+            }
+            fn serialize_map(self, _: Option<usize>) -> Result<Map, Error> {
+                Ok(Map {
+                    entries: Vec::new(),
+                    key: None,
+                })
+            }
+            unsupported! {
+                serialize_bool(bool) -> Value;
+                serialize_f32(f32) -> Value;
+                serialize_f64(f64) -> Value;
+                serialize_char(char) -> Value;
+                serialize_bytes(&[u8]) -> Value;
+                serialize_unit_struct(&'static str) -> Value;
+                serialize_unit_variant(&'static str, u32, &'static str) -> Value;
+                serialize_seq(Option<usize>) -> Impossible<Value, Error>;
+                serialize_tuple(usize) -> Impossible<Value, Error>;
+                serialize_tuple_struct(&'static str, usize) -> Impossible<Value, Error>;
+                serialize_tuple_variant(&'static str, u32, &'static str, usize) -> Impossible<Value, Error>;
+                serialize_struct(&'static str, usize) -> Impossible<Value, Error>;
+                serialize_struct_variant(&'static str, u32, &'static str, usize) -> Impossible<Value, Error>;
+            }
+        }
+
+        pub struct Map {
+            entries: Vec<(Value, Value)>,
+            key: Option<Value>,
+        }
+
+        impl SerializeMap for Map {
+            type Ok = Value;
+            type Error = Error;
+
+            fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), Error> {
+                self.key = Some(key.serialize(Serializer)?);
+                Ok(())
+            }
+            fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+                let key = self.key.take().expect("key before value");
+                self.entries.push((key, value.serialize(Serializer)?));
+                Ok(())
+            }
+            fn end(self) -> Result<Value, Error> {
+                Ok(Value::Map(self.entries))
+            }
+        }
+
+        impl<'de> de::Deserializer<'de> for Value {
+            type Error = Error;
+
+            fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                match self {
+                    Value::Int(int) => match u64::try_from(int) {
+                        Ok(int) => visitor.visit_u64(int),
+                        Err(_) => visitor.visit_i64(int),
+                    },
+                    Value::Str(string) => visitor.visit_string(string),
+                    Value::Null => visitor.visit_unit(),
+                    Value::Map(entries) => visitor.visit_map(Entries {
+                        entries: entries.into_iter(),
+                        value: None,
+                    }),
+                }
+            }
+            fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                match self {
+                    Value::Null => visitor.visit_none(),
+                    value => visitor.visit_some(value),
+                }
+            }
+            forward_to_deserialize_any! {
+                bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+                unit unit_struct newtype_struct seq tuple tuple_struct map struct enum identifier
+                ignored_any
+            }
+        }
+
+        struct Entries {
+            entries: std::vec::IntoIter<(Value, Value)>,
+            value: Option<Value>,
+        }
+
+        impl<'de> MapAccess<'de> for Entries {
+            type Error = Error;
+
+            fn next_key_seed<K: DeserializeSeed<'de>>(
+                &mut self,
+                seed: K,
+            ) -> Result<Option<K::Value>, Error> {
+                match self.entries.next() {
+                    Some((key, value)) => {
+                        self.value = Some(value);
+                        seed.deserialize(key).map(Some)
+                    }
+                    None => Ok(None),
+                }
+            }
+            fn next_value_seed<V: DeserializeSeed<'de>>(
+                &mut self,
+                seed: V,
+            ) -> Result<V::Value, Error> {
+                seed.deserialize(self.value.take().expect("value after key"))
+            }
+        }
+    }
+
+    #[test]
+    fn integer_keys() {
+        #[compact(keys = "integer", accept_verbose)]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct Event {
+            event_id: i32,
+            user_id: Option<i32>,
+        }
+
+        let e = Event {
+            event_id: 1,
+            user_id: Some(2),
+        };
+        let ser = serde_json::to_string(&e).unwrap();
+        assert_eq!(ser, r#"{"0":1,"1":2}"#);
+        test_serde!(Event, e);
+        let de: Event = serde_json::from_str(r#"{"event_id":1,"user_id":2}"#).unwrap();
+        assert_eq!(de, e);
+        let de: Event = serde_json::from_str(r#"{"0":1}"#).unwrap();
+        assert_eq!(de.user_id, None);
+        assert!(serde_json::from_str::<Event>(r#"{"1":2}"#).is_err());
+
+        use binary::Value::{Int, Map};
+        let value = binary::to_value(&e);
+        assert_eq!(value, Map(vec![(Int(0), Int(1)), (Int(1), Int(2))]));
+        assert_eq!(binary::from_value::<Event>(value).unwrap(), e);
+        let de: Event = binary::from_value(Map(vec![(Int(0), Int(1))])).unwrap();
+        assert_eq!(de.user_id, None);
+
+        // Previous integer keys are read as integers.
+        #[compact(keys = "integer", previous(event_id = "5"))]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct Migrated {
+            event_id: i32,
+            user_id: Option<i32>,
+        }
+
+        let old = Map(vec![(Int(5), Int(1)), (Int(1), Int(2))]);
+        let de: Migrated = binary::from_value(old).unwrap();
+        assert_eq!(
+            de,
+            Migrated {
+                event_id: 1,
+                user_id: Some(2),
+            }
+        );
+        let de: Migrated = serde_json::from_str(r#"{"5":1}"#).unwrap();
+        assert_eq!(de.event_id, 1);
+
+        // Only missing `Option` fields are filled in.
+        #[compact(keys = "integer")]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct Marked {
+            id: i32,
+            marker: (),
+        }
+
+        assert!(serde_json::from_str::<Marked>(r#"{"0":1}"#).is_err());
+
+        #[compact(keys = "integer")]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        enum Query {
+            Cancel,
+            Confirm { event_id: i32 },
+            Ping(u8),
+        }
+
+        assert_eq!(serde_json::to_string(&Query::Cancel).unwrap(), "0");
+        let q = Query::Confirm { event_id: 1 };
+        assert_eq!(serde_json::to_string(&q).unwrap(), r#"{"1":{"3":1}}"#);
+        test_serde!(Query, q);
+        test_serde!(Query, Query::Cancel);
+        test_serde!(Query, Query::Ping(5));
+        assert_eq!(binary::to_value(&Query::Cancel), Int(0));
+        let value = binary::to_value(&q);
+        assert_eq!(value, Map(vec![(Int(1), Map(vec![(Int(3), Int(1))]))]));
+        assert_eq!(binary::from_value::<Query>(value).unwrap(), q);
+        let ping = binary::to_value(&Query::Ping(5));
+        assert_eq!(binary::from_value::<Query>(ping).unwrap(), Query::Ping(5));
+    }
+
+    #[test]
+    fn serde_renames() {
+        #[compact]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct Event {
+            #[serde(rename = "id")]
+            event_id: i32,
+            #[serde(alias = "a")]
+            user_id: i32,
+            #[serde(rename(serialize = "t", deserialize = "time"))]
+            timestamp: i32,
+            venue_id: i32,
+        }
+
+        let e = Event {
+            event_id: 1,
+            user_id: 2,
+            timestamp: 3,
+            venue_id: 4,
+        };
+        let ser = serde_json::to_string(&e).unwrap();
+        assert_eq!(ser, r#"{"id":1,"b":2,"t":3,"c":4}"#);
+        let de: Event = serde_json::from_str(r#"{"id":1,"a":2,"time":3,"c":4}"#).unwrap();
+        assert_eq!(de, e);
+    }
+
+    #[test]
+    fn rename_all() {
+        #[compact(keep = ["user_id"], accept_verbose)]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct Event {
+            event_id: i32,
+            user_id: i32,
+        }
+
+        let e = Event {
+            event_id: 1,
+            user_id: 2,
+        };
+        let ser = serde_json::to_string(&e).unwrap();
+        assert_eq!(ser, r#"{"a":1,"userId":2}"#);
+        test_serde!(Event, e);
+        let de: Event = serde_json::from_str(r#"{"eventId":1,"userId":2}"#).unwrap();
+        assert_eq!(de, e);
+
+        #[compact(fields)]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        #[serde(rename_all = "snake_case")]
+        enum Query {
+            CancelAll,
+            Confirm { event_id: i32 },
+        }
+
+        assert_eq!(
+            serde_json::to_string(&Query::CancelAll).unwrap(),
+            r#""cancel_all""#
+        );
+        let q = Query::Confirm { event_id: 1 };
+        assert_eq!(serde_json::to_string(&q).unwrap(), r#"{"confirm":{"a":1}}"#);
+        test_serde!(Query, q);
+
+        // All fields are compacted, camelCase data is still read.
+        #[compact(accept_verbose)]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct Migrated {
+            event_id: i32,
+            user_id: i32,
+        }
+
+        let m = Migrated {
+            event_id: 1,
+            user_id: 2,
+        };
+        assert_eq!(serde_json::to_string(&m).unwrap(), r#"{"a":1,"b":2}"#);
+        let de: Migrated = serde_json::from_str(r#"{"eventId":1,"userId":2}"#).unwrap();
+        assert_eq!(de, m);
     }
 }